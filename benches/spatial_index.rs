@@ -0,0 +1,97 @@
+//! Compares the spatial index against the brute-force pair loop it replaced
+//! for the slime repulsion and chain-range checks.
+
+use bevy::prelude::*;
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+
+#[path = "../src/spatial.rs"]
+#[allow(dead_code)]
+mod spatial;
+
+use spatial::SpatialIndex;
+
+const REPULSION_DISTANCE: f32 = 30.0;
+
+/// Slimes spread over roughly the area of a busy screen.
+fn slimes(count: u32) -> Vec<(Entity, Vec2)> {
+	let side = (count as f32).sqrt() * 25.0;
+	(0..count)
+		.map(|i| {
+			let x = (i.wrapping_mul(2_654_435_761) % 10_000) as f32 / 10_000.0;
+			let y = (i.wrapping_mul(40_503) % 10_000) as f32 / 10_000.0;
+			(Entity::from_raw(i), Vec2::new(x, y) * side)
+		})
+		.collect()
+}
+
+fn repulsion(c: &mut Criterion) {
+	let mut group = c.benchmark_group("repulsion_pairs");
+	for count in [100, 1_000, 5_000] {
+		let slimes = slimes(count);
+		group.bench_with_input(BenchmarkId::new("grid", count), &slimes, |b, slimes| {
+			let mut index = SpatialIndex::default();
+			b.iter(|| {
+				index.rebuild(slimes.iter().copied());
+				let mut pairs = 0;
+				for (e1, p1) in index.iter() {
+					for (e2, _) in index.within(p1, REPULSION_DISTANCE) {
+						pairs += (e1 != e2) as u32;
+					}
+				}
+				black_box(pairs)
+			});
+		});
+		if count <= 1_000 {
+			group.bench_with_input(
+				BenchmarkId::new("brute_force", count),
+				&slimes,
+				|b, slimes| {
+					b.iter(|| {
+						let mut pairs = 0;
+						for (e1, p1) in slimes {
+							for (e2, p2) in slimes {
+								let close =
+									e1 != e2 && p1.distance(*p2) <= REPULSION_DISTANCE;
+								pairs += close as u32;
+							}
+						}
+						black_box(pairs)
+					});
+				},
+			);
+		}
+	}
+	group.finish();
+}
+
+fn chain_range(c: &mut Criterion) {
+	let mut group = c.benchmark_group("chain_range");
+	for count in [100, 1_000, 5_000] {
+		let slimes = slimes(count);
+		let mut index = SpatialIndex::default();
+		index.rebuild(slimes.iter().copied());
+		let center = slimes[0].1;
+		group.bench_with_input(BenchmarkId::new("grid", count), &index, |b, index| {
+			b.iter(|| black_box(index.within(center, 125.0).count()));
+		});
+		group.bench_with_input(
+			BenchmarkId::new("brute_force", count),
+			&slimes,
+			|b, slimes| {
+				b.iter(|| {
+					black_box(
+						slimes
+							.iter()
+							.filter(|(_, p)| p.distance(center) <= 125.0)
+							.count(),
+					)
+				});
+			},
+		);
+	}
+	group.finish();
+}
+
+criterion_group!(benches, repulsion, chain_range);
+criterion_main!(benches);
@@ -0,0 +1,369 @@
+//! What each slime is trying to do. Slimes far from the player wander, drifting
+//! slowly its way; inside their aggro radius they chase it, and when a chain
+//! pops nearby they run from the blast for a moment. [`AiTuning`] holds the
+//! numbers for each [`Archetype`].
+
+use crate::archetype::Archetype;
+use crate::chain::Chained;
+use crate::events::SlimePopped;
+use crate::game_rng::GameRng;
+use crate::interpolation::ticks;
+use crate::spatial::SpatialIndex;
+use crate::tick_input::SlimeId;
+use crate::{
+	Enemy, Player, Simulation, Velocity, move_enemy, prevent_enemies_from_collision,
+};
+use bevy::prelude::*;
+use rand::Rng;
+use std::time::Duration;
+
+pub struct AiPlugin;
+impl Plugin for AiPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<AiTuning>();
+		app.add_systems(
+			FixedUpdate,
+			(
+				update_ai_states.before(move_enemy),
+				cohere
+					.after(move_enemy)
+					.before(prevent_enemies_from_collision),
+			)
+				.in_set(Simulation::Movement),
+		);
+	}
+}
+
+/// How one archetype behaves. Speeds are multiples of the slime's own.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AiParams {
+	/// Slimes closer than this to the player chase it.
+	pub aggro_radius: f32,
+	/// Chasing slimes further than this go back to wandering.
+	pub give_up_radius: f32,
+	pub wander_speed: f32,
+	/// The most a wandering slime turns in one tick, in radians.
+	pub wander_turn: f32,
+	/// How strongly a wandering slime still drifts towards the player, from 0
+	/// (not at all) to 1 (as strongly as it wanders).
+	pub wander_pull: f32,
+	/// Pops this close make the slime flee.
+	pub flee_radius: f32,
+	/// Seconds spent fleeing; zero never flees.
+	pub flee_time: f32,
+	pub flee_speed: f32,
+	/// How far away other slimes count as the same cluster.
+	pub cohesion_radius: f32,
+	/// How hard a slime is pulled towards its cluster, per tick.
+	pub cohesion: f32,
+}
+
+impl Default for AiParams {
+	fn default() -> Self {
+		Self {
+			aggro_radius: 650.0,
+			give_up_radius: 950.0,
+			wander_speed: 0.5,
+			wander_turn: 0.08,
+			wander_pull: 0.4,
+			flee_radius: 250.0,
+			flee_time: 1.0,
+			flee_speed: 1.4,
+			cohesion_radius: 90.0,
+			cohesion: 0.02,
+		}
+	}
+}
+
+/// The [`AiParams`] for each archetype.
+#[derive(Resource, Clone, Debug)]
+pub struct AiTuning {
+	pub plain: AiParams,
+	pub darter: AiParams,
+	pub splitter: AiParams,
+	pub armored: AiParams,
+	pub chameleon: AiParams,
+	pub tether_cutter: AiParams,
+	pub boss: AiParams,
+}
+
+impl Default for AiTuning {
+	fn default() -> Self {
+		let plain = AiParams::default();
+		Self {
+			plain,
+			darter: AiParams {
+				aggro_radius: 800.0,
+				give_up_radius: 1100.0,
+				cohesion: 0.0,
+				..plain
+			},
+			splitter: plain,
+			armored: AiParams {
+				flee_time: 0.0,
+				cohesion: 0.04,
+				..plain
+			},
+			chameleon: AiParams {
+				wander_turn: 0.15,
+				..plain
+			},
+			// Tether-cutters are after the chain, so they hunt from further out
+			// and don't scare easily.
+			tether_cutter: AiParams {
+				aggro_radius: 900.0,
+				give_up_radius: 1300.0,
+				flee_time: 0.4,
+				cohesion: 0.0,
+				..plain
+			},
+			// Bosses always know where the player is and stand their ground.
+			boss: AiParams {
+				aggro_radius: f32::INFINITY,
+				give_up_radius: f32::INFINITY,
+				flee_time: 0.0,
+				cohesion: 0.0,
+				..plain
+			},
+		}
+	}
+}
+
+impl AiTuning {
+	pub fn get(&self, archetype: Archetype) -> &AiParams {
+		match archetype {
+			Archetype::Plain => &self.plain,
+			Archetype::Darter => &self.darter,
+			Archetype::Splitter => &self.splitter,
+			Archetype::Armored => &self.armored,
+			Archetype::Chameleon => &self.chameleon,
+			Archetype::TetherCutter => &self.tether_cutter,
+			Archetype::Boss => &self.boss,
+		}
+	}
+}
+
+#[derive(Component, Clone, Debug, PartialEq)]
+pub enum AiState {
+	/// Ambling about. A fresh slime has no heading yet and sets off towards
+	/// the player.
+	Wander {
+		heading: Vec2,
+	},
+	Aggro,
+	Flee {
+		from: Vec2,
+		timer: Timer,
+	},
+}
+
+impl Default for AiState {
+	fn default() -> Self {
+		AiState::Wander {
+			heading: Vec2::ZERO,
+		}
+	}
+}
+
+impl AiState {
+	/// Which way the slime wants to go from `position`, and how fast compared
+	/// to its usual speed.
+	pub fn steer(
+		&self,
+		position: Vec2,
+		player: Vec2,
+		params: &AiParams,
+	) -> (Vec2, f32) {
+		match self {
+			AiState::Wander { heading } => {
+				let pull = (player - position).normalize_or_zero() * params.wander_pull;
+				((*heading + pull).normalize_or_zero(), params.wander_speed)
+			}
+			AiState::Aggro => ((player - position).normalize_or_zero(), 1.0),
+			AiState::Flee { from, .. } => {
+				((position - *from).normalize_or_zero(), params.flee_speed)
+			}
+		}
+	}
+
+	/// Where the slime goes next, given the `offset` from it to the player and
+	/// the nearest pop within its flee radius this tick. Chained slimes always
+	/// follow the player.
+	fn next(
+		&mut self,
+		params: &AiParams,
+		offset: Vec2,
+		pop: Option<Vec2>,
+		chained: bool,
+		elapsed: Duration,
+		rng: &mut impl Rng,
+	) {
+		if chained {
+			*self = AiState::Aggro;
+			return;
+		}
+		if let Some(from) = pop.filter(|_| params.flee_time > 0.0) {
+			let timer = Timer::from_seconds(params.flee_time, TimerMode::Once);
+			*self = AiState::Flee { from, timer };
+			return;
+		}
+		let distance = offset.length();
+		match self {
+			AiState::Flee { timer, .. } => {
+				if timer.tick(elapsed).finished() {
+					*self = AiState::Aggro;
+				}
+			}
+			AiState::Aggro if distance > params.give_up_radius => {
+				*self = AiState::Wander {
+					heading: Vec2::from_angle(
+						rng.gen_range(0.0..std::f32::consts::TAU),
+					),
+				};
+			}
+			AiState::Aggro => {}
+			AiState::Wander { .. } if distance < params.aggro_radius => {
+				*self = AiState::Aggro;
+			}
+			AiState::Wander { heading } if *heading == Vec2::ZERO => {
+				*heading = offset.normalize_or_zero();
+			}
+			AiState::Wander { heading } => {
+				let turn = rng.gen_range(-params.wander_turn..=params.wander_turn);
+				*heading = Vec2::from_angle(turn).rotate(*heading);
+			}
+		}
+	}
+}
+
+/// Moves every slime on to its next state, in spawn order so replays draw the
+/// same wander turns.
+fn update_ai_states(
+	time: Res<Time>,
+	tuning: Res<AiTuning>,
+	mut popped: EventReader<SlimePopped>,
+	mut slimes: Query<(
+		Entity,
+		&SlimeId,
+		&Transform,
+		&Archetype,
+		&mut AiState,
+		Has<Chained>,
+	)>,
+	player: Single<&Transform, (With<Player>, Without<Enemy>)>,
+	mut rng: ResMut<GameRng>,
+) {
+	let pops: Vec<(Entity, Vec2)> = popped
+		.read()
+		.map(|pop| (pop.entity, pop.position.xy()))
+		.collect();
+	let player = player.translation.xy();
+	let rng = rng.gameplay();
+	let mut slimes: Vec<_> = slimes.iter_mut().collect();
+	slimes.sort_unstable_by_key(|(_, id, ..)| **id);
+	for (slime, _, transform, archetype, mut state, chained) in slimes {
+		let params = tuning.get(*archetype);
+		let position = transform.translation.xy();
+		// An armoured slime that survived its own pop has nothing to run from.
+		let pop = pops
+			.iter()
+			.filter(|(popped, _)| *popped != slime)
+			.map(|(_, pop)| *pop)
+			.filter(|pop| pop.distance(position) <= params.flee_radius)
+			.min_by(|a, b| a.distance(position).total_cmp(&b.distance(position)));
+		state.next(params, player - position, pop, chained, time.delta(), rng);
+	}
+}
+
+/// Boids-style cohesion: unchained slimes lean towards the middle of the slimes
+/// around them, so clusters hold together as they wander.
+fn cohere(
+	time: Res<Time>,
+	tuning: Res<AiTuning>,
+	spatial_index: Res<SpatialIndex>,
+	mut slimes: Query<(&Archetype, &AiState, &mut Velocity), Without<Chained>>,
+) {
+	let ticks = ticks(&time);
+	for (slime, position) in spatial_index.iter() {
+		let Ok((archetype, state, mut velocity)) = slimes.get_mut(slime) else {
+			continue;
+		};
+		let params = tuning.get(*archetype);
+		if params.cohesion <= 0.0 || matches!(state, AiState::Flee { .. }) {
+			continue;
+		}
+		let (sum, count) = spatial_index
+			.within(position, params.cohesion_radius)
+			.filter(|(other, _)| *other != slime)
+			.fold((Vec2::ZERO, 0), |(sum, count), (_, at)| {
+				(sum + at, count + 1)
+			});
+		if count == 0 {
+			continue;
+		}
+		let middle = sum / count as f32;
+		let pull = (middle - position).normalize_or_zero() * params.cohesion * ticks;
+		velocity.0 += pull.extend(0.0);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::SeedableRng;
+	use rand_chacha::ChaCha8Rng;
+
+	#[test]
+	fn slimes_wander_chase_and_flee() {
+		let params = AiParams::default();
+		let mut rng = ChaCha8Rng::seed_from_u64(1);
+		let tick = Duration::from_millis(100);
+		let mut state = AiState::default();
+
+		state.next(&params, Vec2::X * 2000.0, None, false, tick, &mut rng);
+		assert_eq!(state, AiState::Wander { heading: Vec2::X });
+		state.next(&params, Vec2::X * 600.0, None, false, tick, &mut rng);
+		assert_eq!(state, AiState::Aggro);
+		// Still chasing until the player is well out of range.
+		state.next(&params, Vec2::X * 800.0, None, false, tick, &mut rng);
+		assert_eq!(state, AiState::Aggro);
+
+		state.next(
+			&params,
+			Vec2::X * 800.0,
+			Some(Vec2::ZERO),
+			false,
+			tick,
+			&mut rng,
+		);
+		assert!(matches!(state, AiState::Flee { .. }));
+		for _ in 0..9 {
+			state.next(&params, Vec2::X * 800.0, None, false, tick, &mut rng);
+		}
+		assert!(matches!(state, AiState::Flee { .. }));
+		state.next(&params, Vec2::X * 800.0, None, false, tick, &mut rng);
+		assert_eq!(state, AiState::Aggro);
+
+		state.next(&params, Vec2::X * 2000.0, None, false, tick, &mut rng);
+		assert!(matches!(state, AiState::Wander { .. }));
+		state.next(&params, Vec2::X * 2000.0, None, true, tick, &mut rng);
+		assert_eq!(state, AiState::Aggro);
+	}
+
+	#[test]
+	fn armour_never_flees() {
+		let params = AiTuning::default().armored;
+		let mut rng = ChaCha8Rng::seed_from_u64(1);
+		let mut state = AiState::Aggro;
+		let pop = Some(Vec2::ZERO);
+		state.next(
+			&params,
+			Vec2::X * 100.0,
+			pop,
+			false,
+			Duration::ZERO,
+			&mut rng,
+		);
+		assert_eq!(state, AiState::Aggro);
+	}
+}
@@ -0,0 +1,324 @@
+//! Slime archetypes. Most slimes are [`Archetype::Plain`] and walk straight at
+//! the player; the others add a twist to how they move, pop or meet the chain.
+
+use crate::chain::{ChainGraph, Chained};
+use crate::enemy::SpawnEnemy;
+use crate::slime_palette::SlimePalette;
+use crate::tick_input::SlimeId;
+use crate::{Enemy, Simulation, move_enemy, move_enemy_2, release_links};
+use bevy::color::palettes::css;
+use bevy::prelude::*;
+use rand::Rng;
+
+pub struct ArchetypePlugin;
+impl Plugin for ArchetypePlugin {
+	fn build(&self, app: &mut App) {
+		app.add_observer(wind_archetype_clock);
+		app.add_systems(
+			FixedUpdate,
+			(
+				tick_archetype_clocks.before(move_enemy),
+				cut_tethers.after(move_enemy_2),
+			)
+				.in_set(Simulation::Movement),
+		);
+	}
+}
+
+/// A badge on each slime that isn't plain, so its archetype can be told apart.
+pub struct ArchetypeVisualsPlugin;
+impl Plugin for ArchetypeVisualsPlugin {
+	fn build(&self, app: &mut App) {
+		app.add_observer(dress_archetype);
+		app.add_systems(Update, (pulse_chameleon_badges, flash_darter_badges));
+	}
+}
+
+/// How often a darter bursts forward, and for how long.
+const DART_PERIOD: f32 = 2.5;
+const DART_TIME: f32 = 0.5;
+const DART_BOOST: f32 = 3.0;
+/// How often a chameleon swaps its charge.
+const FLIP_PERIOD: f32 = 4.0;
+/// How close a tether-cutter has to get to a chain segment to sever it.
+const CUT_RADIUS: f32 = 12.0;
+
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Archetype {
+	#[default]
+	Plain,
+	/// Bursts forward every few seconds.
+	Darter,
+	/// Pops into two slimes with half its charge.
+	Splitter,
+	/// Loses its armour to the first detonation and pops in the second.
+	Armored,
+	/// Swaps its polarity every few seconds.
+	Chameleon,
+	/// Severs any chain it touches.
+	TetherCutter,
+	/// Never drawn for a cluster; see [`crate::boss`].
+	Boss,
+}
+
+impl Archetype {
+	/// How often each archetype turns up in a cluster, out of the total.
+	pub const SPAWN_WEIGHTS: [(Archetype, u32); 6] = [
+		(Archetype::Plain, 60),
+		(Archetype::Darter, 10),
+		(Archetype::Splitter, 8),
+		(Archetype::Armored, 8),
+		(Archetype::Chameleon, 8),
+		(Archetype::TetherCutter, 6),
+	];
+
+	pub fn random(rng: &mut impl Rng) -> Self {
+		let total = Self::SPAWN_WEIGHTS.iter().map(|(_, weight)| weight).sum();
+		let mut roll = rng.gen_range(0..total);
+		for (archetype, weight) in Self::SPAWN_WEIGHTS {
+			if roll < weight {
+				return archetype;
+			}
+			roll -= weight;
+		}
+		Archetype::Plain
+	}
+
+	/// How fast a slime goes compared to a plain one, given its
+	/// [`ArchetypeClock`].
+	pub fn speed_factor(self, clock: Option<&ArchetypeClock>) -> f32 {
+		match self {
+			Archetype::Darter if clock.is_some_and(ArchetypeClock::is_darting) => {
+				DART_BOOST
+			}
+			Archetype::Darter => 0.8,
+			Archetype::Armored => 0.7,
+			Archetype::TetherCutter => 1.1,
+			_ => 1.0,
+		}
+	}
+}
+
+/// Times darters' bursts and chameleons' flips.
+#[derive(Component, Debug)]
+pub struct ArchetypeClock(pub Timer);
+
+impl ArchetypeClock {
+	fn is_darting(&self) -> bool {
+		self.0.elapsed_secs() < DART_TIME
+	}
+}
+
+/// Marks a child sprite that shows its parent's archetype.
+#[derive(Component)]
+struct ArchetypeBadge;
+
+/// The slimes a splitter pops into, on either side of where it was.
+pub fn split(enemy: &Enemy, position: Vec2) -> [SpawnEnemy; 2] {
+	let half = enemy.halved();
+	[Vec2::new(-20.0, 0.0), Vec2::new(20.0, 0.0)].map(|offset| SpawnEnemy {
+		position: position + offset,
+		enemy: half,
+		archetype: Archetype::Plain,
+	})
+}
+
+fn wind_archetype_clock(
+	trigger: Trigger<OnInsert, Archetype>,
+	archetypes: Query<&Archetype>,
+	mut commands: Commands,
+) {
+	let Ok(archetype) = archetypes.get(trigger.target()) else {
+		return;
+	};
+	let period = match archetype {
+		Archetype::Darter => DART_PERIOD,
+		Archetype::Chameleon => FLIP_PERIOD,
+		_ => {
+			commands.entity(trigger.target()).remove::<ArchetypeClock>();
+			return;
+		}
+	};
+	commands
+		.entity(trigger.target())
+		.insert(ArchetypeClock(Timer::from_seconds(
+			period,
+			TimerMode::Repeating,
+		)));
+}
+
+/// Chameleons flip when their clock comes round.
+fn tick_archetype_clocks(
+	time: Res<Time>,
+	mut clocks: Query<(Entity, &Archetype, &Enemy, &mut ArchetypeClock)>,
+	mut commands: Commands,
+) {
+	for (slime, archetype, enemy, mut clock) in clocks.iter_mut() {
+		if clock.0.tick(time.delta()).just_finished()
+			&& *archetype == Archetype::Chameleon
+		{
+			commands.entity(slime).insert(enemy.flipped());
+		}
+	}
+}
+
+/// Tether-cutters that touch a chain cut it there, releasing the links from that
+/// point outward. They go in spawn order so replays cut the same way.
+fn cut_tethers(
+	mut commands: Commands,
+	mut chain_graph: ResMut<ChainGraph>,
+	cutters: Query<(&SlimeId, &Archetype, &Transform), Without<Chained>>,
+	transforms: Query<&Transform>,
+) {
+	let mut cutters: Vec<_> = cutters
+		.iter()
+		.filter(|(_, archetype, _)| **archetype == Archetype::TetherCutter)
+		.collect();
+	cutters.sort_unstable_by_key(|(id, ..)| **id);
+	for (_, _, cutter) in cutters {
+		let blade = cutter.translation.xy();
+		let touched = (0..chain_graph.len()).find_map(|index| {
+			chain_graph.edges(index).find_map(|(inner, outer)| {
+				let [inner, outer_at] = transforms.get_many([inner, outer]).ok()?;
+				let distance = distance_to_segment(
+					blade,
+					inner.translation.xy(),
+					outer_at.translation.xy(),
+				);
+				(distance <= CUT_RADIUS).then_some(outer)
+			})
+		});
+		if let Some(outer) = touched {
+			let released = chain_graph.cut_at(outer);
+			release_links(&mut commands, released);
+		}
+	}
+}
+
+fn distance_to_segment(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+	let along = end - start;
+	let t = (point - start).dot(along) / along.length_squared().max(f32::EPSILON);
+	point.distance(start + along * t.clamp(0.0, 1.0))
+}
+
+fn dress_archetype(
+	trigger: Trigger<OnInsert, Archetype>,
+	slimes: Query<(&Archetype, &Enemy, Option<&Children>)>,
+	badges: Query<(), With<ArchetypeBadge>>,
+	palette: Res<SlimePalette>,
+	asset_server: Res<AssetServer>,
+	mut commands: Commands,
+) {
+	let slime = trigger.target();
+	let Ok((archetype, enemy, children)) = slimes.get(slime) else {
+		return;
+	};
+	for child in children.into_iter().flatten() {
+		if badges.contains(*child) {
+			commands.entity(*child).despawn();
+		}
+	}
+	let size = enemy.sprite_size();
+	let slime_sprite = |color: Color, size: Vec2| Sprite {
+		image: asset_server.load("images/slime.png"),
+		rect: Some(Rect::new(0.0, 16.0, 16.0 * 2.0, 16.0 * 2.0)),
+		color,
+		custom_size: Some(size),
+		..default()
+	};
+	let badge = match archetype {
+		Archetype::Plain => return,
+		Archetype::Darter => (
+			Sprite::from_color(Color::WHITE.with_alpha(0.4), Vec2::new(size.x, 3.0)),
+			Transform::from_xyz(-size.x * 0.4, -size.y * 0.3, -0.1),
+		),
+		Archetype::Splitter => (
+			slime_sprite(palette.color_of(enemy).lighter(0.1), size * 0.5),
+			Transform::from_xyz(size.x * 0.35, size.y * 0.35, 0.1),
+		),
+		Archetype::Armored => (
+			Sprite::from_color(Color::from(css::SLATE_GRAY), size * 1.25),
+			Transform::from_xyz(0.0, 0.0, -0.1),
+		),
+		Archetype::Chameleon => (
+			slime_sprite(palette.color_of(&enemy.flipped()), size * 0.5),
+			Transform::from_xyz(0.0, size.y * 0.5, 0.1),
+		),
+		Archetype::TetherCutter => (
+			Sprite {
+				image: asset_server.load("images/chain.png"),
+				color: Color::from(css::CRIMSON),
+				custom_size: Some(Vec2::splat(12.0)),
+				..default()
+			},
+			Transform::from_xyz(0.0, size.y * 0.6, 0.1)
+				.with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_4)),
+		),
+		Archetype::Boss => (
+			Sprite::from_color(Color::from(css::GOLD), Vec2::new(size.x * 0.4, 6.0)),
+			Transform::from_xyz(0.0, size.y * 0.6, 0.1),
+		),
+	};
+	commands.spawn((badge, ArchetypeBadge, Pickable::IGNORE, ChildOf(slime)));
+}
+
+/// A chameleon's badge shows the colour it will turn next, firming up as the
+/// flip gets closer.
+fn pulse_chameleon_badges(
+	slimes: Query<(&Archetype, &Enemy, &ArchetypeClock)>,
+	mut badges: Query<(&ChildOf, &mut Sprite), With<ArchetypeBadge>>,
+	palette: Res<SlimePalette>,
+) {
+	for (parent, mut sprite) in badges.iter_mut() {
+		if let Ok((Archetype::Chameleon, enemy, clock)) = slimes.get(parent.parent()) {
+			let next = palette.color_of(&enemy.flipped());
+			sprite.color = next.with_alpha(clock.0.fraction());
+		}
+	}
+}
+
+/// A darter's streak only shows while it is bursting.
+fn flash_darter_badges(
+	slimes: Query<(&Archetype, &ArchetypeClock)>,
+	mut badges: Query<(&ChildOf, &mut Visibility), With<ArchetypeBadge>>,
+) {
+	for (parent, mut visibility) in badges.iter_mut() {
+		if let Ok((Archetype::Darter, clock)) = slimes.get(parent.parent()) {
+			*visibility = if clock.is_darting() {
+				Visibility::Inherited
+			} else {
+				Visibility::Hidden
+			};
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::SeedableRng;
+	use rand_chacha::ChaCha8Rng;
+
+	#[test]
+	fn segments_are_measured_to_their_nearest_point() {
+		let (start, end) = (Vec2::ZERO, Vec2::new(100.0, 0.0));
+		assert_eq!(distance_to_segment(Vec2::new(50.0, 5.0), start, end), 5.0);
+		assert_eq!(distance_to_segment(Vec2::new(-3.0, 4.0), start, end), 5.0);
+		assert_eq!(
+			distance_to_segment(Vec2::new(1.0, 1.0), start, start),
+			2f32.sqrt()
+		);
+	}
+
+	#[test]
+	fn every_archetype_turns_up() {
+		let mut rng = ChaCha8Rng::seed_from_u64(1);
+		let drawn: Vec<Archetype> =
+			(0..1000).map(|_| Archetype::random(&mut rng)).collect();
+		for (archetype, _) in Archetype::SPAWN_WEIGHTS {
+			assert!(drawn.contains(&archetype), "{archetype:?} never spawned");
+		}
+		let plain = drawn.iter().filter(|a| **a == Archetype::Plain).count();
+		assert!(plain > 500, "{plain} plain slimes");
+	}
+}
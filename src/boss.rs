@@ -0,0 +1,359 @@
+//! Boss slimes. Every so often a big, heavy slime turns up that slams the
+//! ground and calls in minions. Popping it as a link of a balanced chain takes
+//! a point of its health and gives it a new charge; it only pops for good once
+//! its health runs out.
+
+use crate::archetype::Archetype;
+use crate::detonation::step_detonations;
+use crate::enemy::{
+	SpawnEnemy, SpawnView, handle_spawn_enemy, pick_spawn_point, random_cluster,
+	spawn_slime,
+};
+use crate::events::{BossArrived, BossDefeated, BossHit, BossSlammed, PlayerHit};
+use crate::game_mode::{GameMode, RunClock};
+use crate::game_rng::GameRng;
+use crate::menus::GameState;
+use crate::slime_palette::SlimePalette;
+use crate::spatial::SpatialIndex;
+use crate::tick_input::SlimeId;
+use crate::waves::{CurrentWave, WaveSettings};
+use crate::{
+	Enemy, EnemyPolarity, MaxInternalVelocity, Player, Score, Simulation, release_links,
+};
+use bevy::color::palettes::css;
+use bevy::prelude::*;
+use std::time::Duration;
+
+pub struct BossPlugin;
+impl Plugin for BossPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<BossSchedule>();
+		app.add_systems(OnEnter(GameState::Game), reset_boss_schedule);
+		app.add_systems(
+			FixedUpdate,
+			spawn_bosses
+				.after(handle_spawn_enemy)
+				.in_set(Simulation::Spawn),
+		);
+		app.add_systems(
+			FixedUpdate,
+			apply_boss_hits
+				.after(step_detonations)
+				.in_set(Simulation::Detonation),
+		);
+		app.add_systems(
+			FixedUpdate,
+			(run_boss_attacks, add_boss_bonus).in_set(Simulation::Outcome),
+		);
+	}
+}
+
+/// The ring a boss's slam will cover, growing as it winds up.
+pub struct BossVisualsPlugin;
+impl Plugin for BossVisualsPlugin {
+	fn build(&self, app: &mut App) {
+		app.add_observer(dress_boss);
+		app.add_systems(Update, grow_slam_rings);
+	}
+}
+
+/// When the first boss turns up, and how long after each one the next does.
+const FIRST_BOSS: Duration = Duration::from_secs(90);
+const BOSS_INTERVAL: Duration = Duration::from_secs(120);
+/// Bosses weigh this much in the balance, so it takes several slimes to
+/// cancel one out.
+const BOSS_MAGNITUDE: u8 = 5;
+const BOSS_SPEED: f32 = 0.6;
+/// Health of the first boss; each later one has a point more, up to the max.
+const BOSS_HEALTH: u32 = 3;
+const MAX_BOSS_HEALTH: u32 = 6;
+/// Points for finishing a boss off, on top of the detonation's.
+const BOSS_BONUS: u32 = 25;
+/// Seconds between attacks.
+const ATTACK_PERIOD: f32 = 5.0;
+/// Seconds a slam is telegraphed before it lands.
+const SLAM_WINDUP: f32 = 1.2;
+const SLAM_RADIUS: f32 = 180.0;
+/// Slimes per minion cluster, on average.
+const MINIONS: f32 = 3.0;
+
+/// What a boss does, in turn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BossAttack {
+	Slam,
+	SpawnMinions,
+}
+
+const ATTACK_PATTERN: [BossAttack; 3] =
+	[BossAttack::SpawnMinions, BossAttack::Slam, BossAttack::Slam];
+
+#[derive(Component, Debug)]
+pub struct Boss {
+	pub health: u32,
+	pub max_health: u32,
+	attack_timer: Timer,
+	attacks: usize,
+	/// Counts down to a slam that has been telegraphed.
+	slam: Option<Timer>,
+}
+
+impl Boss {
+	pub fn new(health: u32) -> Self {
+		Self {
+			health,
+			max_health: health,
+			attack_timer: Timer::from_seconds(ATTACK_PERIOD, TimerMode::Repeating),
+			attacks: 0,
+			slam: None,
+		}
+	}
+
+	/// How far a slam has wound up, from 0 to 1, if one is coming.
+	pub fn slam_progress(&self) -> Option<f32> {
+		self.slam.as_ref().map(Timer::fraction)
+	}
+
+	/// Advances the attack pattern, returning the attack that lands now.
+	/// Slams are telegraphed first and land [`SLAM_WINDUP`] seconds later.
+	pub fn tick(&mut self, delta: Duration) -> Option<BossAttack> {
+		if let Some(slam) = &mut self.slam {
+			if slam.tick(delta).finished() {
+				self.slam = None;
+				return Some(BossAttack::Slam);
+			}
+			return None;
+		}
+		if !self.attack_timer.tick(delta).just_finished() {
+			return None;
+		}
+		let attack = ATTACK_PATTERN[self.attacks % ATTACK_PATTERN.len()];
+		self.attacks += 1;
+		match attack {
+			BossAttack::Slam => {
+				self.slam = Some(Timer::from_seconds(SLAM_WINDUP, TimerMode::Once));
+				None
+			}
+			BossAttack::SpawnMinions => Some(attack),
+		}
+	}
+}
+
+/// When the next boss is due, in run time, and how many have come so far.
+#[derive(Resource, Debug)]
+struct BossSchedule {
+	next: Duration,
+	count: u32,
+}
+
+impl Default for BossSchedule {
+	fn default() -> Self {
+		Self {
+			next: FIRST_BOSS,
+			count: 0,
+		}
+	}
+}
+
+fn reset_boss_schedule(mut schedule: ResMut<BossSchedule>) {
+	*schedule = BossSchedule::default();
+}
+
+/// Brings in the next boss when it is due, unless one is still around.
+fn spawn_bosses(
+	run_clock: Res<RunClock>,
+	mut schedule: ResMut<BossSchedule>,
+	bosses: Query<(), With<Boss>>,
+	player: Single<&Transform, With<Player>>,
+	palette: Res<SlimePalette>,
+	spawn_view: Res<SpawnView>,
+	spatial_index: Res<SpatialIndex>,
+	mut commands: Commands,
+	mut rng: ResMut<GameRng>,
+	mut boss_arrived: EventWriter<BossArrived>,
+) {
+	if run_clock.0 < schedule.next || !bosses.is_empty() {
+		return;
+	}
+	let rng = rng.gameplay();
+	let taken: Vec<Vec2> = spatial_index.iter().map(|(_, position)| position).collect();
+	let Some(position) =
+		pick_spawn_point(rng, player.translation.xy(), spawn_view.0, &taken)
+	else {
+		return;
+	};
+	let enemy = Enemy {
+		enemy_color: palette.random_color(rng),
+		enemy_polarity: EnemyPolarity::random(rng),
+		magnitude: BOSS_MAGNITUDE,
+	};
+	let health = (BOSS_HEALTH + schedule.count).min(MAX_BOSS_HEALTH);
+	let boss = spawn_slime(
+		&mut commands,
+		position,
+		enemy,
+		MaxInternalVelocity(BOSS_SPEED),
+	);
+	commands
+		.entity(boss)
+		.insert((Archetype::Boss, Boss::new(health)));
+	schedule.next = run_clock.0 + BOSS_INTERVAL;
+	schedule.count += 1;
+	boss_arrived.write(BossArrived { entity: boss });
+}
+
+/// A boss that survives a hit drops out of the chain with a new charge, so the
+/// next chain has to balance something else.
+fn apply_boss_hits(
+	mut boss_hits: EventReader<BossHit>,
+	mut bosses: Query<(&mut Boss, &Enemy, &Transform)>,
+	palette: Res<SlimePalette>,
+	mut commands: Commands,
+	mut rng: ResMut<GameRng>,
+	mut boss_defeated: EventWriter<BossDefeated>,
+) {
+	let rng = rng.gameplay();
+	for BossHit { entity } in boss_hits.read() {
+		let Ok((mut boss, enemy, transform)) = bosses.get_mut(*entity) else {
+			continue;
+		};
+		boss.health = boss.health.saturating_sub(1);
+		if boss.health == 0 {
+			commands.entity(*entity).despawn();
+			boss_defeated.write(BossDefeated {
+				entity: *entity,
+				position: transform.translation,
+			});
+			continue;
+		}
+		release_links(&mut commands, [*entity]);
+		commands.entity(*entity).insert(Enemy {
+			enemy_color: palette.random_color(rng),
+			enemy_polarity: EnemyPolarity::random(rng),
+			..*enemy
+		});
+	}
+}
+
+/// Lands slams on a player in range and calls in minions, boss by boss in
+/// spawn order so replays draw the same minions.
+fn run_boss_attacks(
+	time: Res<Time>,
+	mode: Res<GameMode>,
+	current_wave: Res<CurrentWave>,
+	palette: Res<SlimePalette>,
+	player: Single<&Transform, With<Player>>,
+	mut bosses: Query<(Entity, &SlimeId, &Transform, &mut Boss)>,
+	mut rng: ResMut<GameRng>,
+	mut game_state: ResMut<NextState<GameState>>,
+	mut player_hit: EventWriter<PlayerHit>,
+	mut boss_slammed: EventWriter<BossSlammed>,
+	mut spawn_enemy: EventWriter<SpawnEnemy>,
+) {
+	let rng = rng.gameplay();
+	let mut bosses: Vec<_> = bosses.iter_mut().collect();
+	bosses.sort_unstable_by_key(|(_, id, ..)| **id);
+	for (entity, _, transform, mut boss) in bosses {
+		let position = transform.translation;
+		match boss.tick(time.delta()) {
+			Some(BossAttack::Slam) => {
+				boss_slammed.write(BossSlammed {
+					entity,
+					position,
+					radius: SLAM_RADIUS,
+				});
+				let in_range =
+					player.translation.xy().distance(position.xy()) <= SLAM_RADIUS;
+				if in_range && mode.player_can_die() {
+					game_state.set(GameState::Shop);
+					player_hit.write(PlayerHit { slime: entity });
+				}
+			}
+			Some(BossAttack::SpawnMinions) => {
+				let wave = WaveSettings {
+					cluster_size: MINIONS,
+					..current_wave.0
+				};
+				spawn_enemy.write_batch(random_cluster(
+					rng,
+					&palette,
+					&wave,
+					position.xy(),
+				));
+			}
+			None => {}
+		}
+	}
+}
+
+fn add_boss_bonus(
+	mut boss_defeated: EventReader<BossDefeated>,
+	mut score: ResMut<Score>,
+) {
+	for _ in boss_defeated.read() {
+		score.0 += BOSS_BONUS;
+	}
+}
+
+/// The telegraph for a boss's slam.
+#[derive(Component)]
+struct SlamRing;
+
+fn dress_boss(
+	trigger: Trigger<OnAdd, Boss>,
+	mut meshes: ResMut<Assets<Mesh>>,
+	mut materials: ResMut<Assets<ColorMaterial>>,
+	mut commands: Commands,
+) {
+	commands.spawn((
+		Mesh2d(meshes.add(Circle::new(SLAM_RADIUS))),
+		MeshMaterial2d(materials.add(Color::from(css::CRIMSON).with_alpha(0.2))),
+		Transform::from_xyz(0.0, 0.0, -0.5),
+		Visibility::Hidden,
+		SlamRing,
+		Pickable::IGNORE,
+		ChildOf(trigger.target()),
+	));
+}
+
+fn grow_slam_rings(
+	bosses: Query<&Boss>,
+	mut rings: Query<(&ChildOf, &mut Transform, &mut Visibility), With<SlamRing>>,
+) {
+	for (parent, mut transform, mut visibility) in rings.iter_mut() {
+		let progress = bosses
+			.get(parent.parent())
+			.ok()
+			.and_then(Boss::slam_progress);
+		let Some(progress) = progress else {
+			*visibility = Visibility::Hidden;
+			continue;
+		};
+		*visibility = Visibility::Inherited;
+		transform.scale = Vec3::splat(progress).with_z(1.0);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn bosses_call_minions_then_telegraph_two_slams() {
+		let mut boss = Boss::new(3);
+		let period = Duration::from_secs_f32(ATTACK_PERIOD);
+		let windup = Duration::from_secs_f32(SLAM_WINDUP);
+
+		assert_eq!(boss.tick(period), Some(BossAttack::SpawnMinions));
+		assert_eq!(boss.tick(period), None);
+		assert_eq!(boss.slam_progress(), Some(0.0));
+		assert_eq!(boss.tick(windup / 2), None);
+		let halfway = boss.slam_progress().unwrap();
+		assert!((halfway - 0.5).abs() < 1e-3, "{halfway}");
+		assert_eq!(boss.tick(windup / 2), Some(BossAttack::Slam));
+		assert_eq!(boss.slam_progress(), None);
+
+		assert_eq!(boss.tick(period), None);
+		assert_eq!(boss.tick(windup), Some(BossAttack::Slam));
+		assert_eq!(boss.tick(period), Some(BossAttack::SpawnMinions));
+	}
+}
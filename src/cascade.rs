@@ -0,0 +1,129 @@
+//! Rules for how a popped slime ignites the unchained slimes around it during
+//! a chain reaction.
+
+use crate::Enemy;
+use bevy::prelude::*;
+
+/// Which nearby slimes a popping slime ignites.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlastRule {
+	SameColor,
+	OppositePolarity,
+	SameColorOppositePolarity,
+}
+
+impl BlastRule {
+	pub fn matches(self, source: &Enemy, target: &Enemy) -> bool {
+		let same_color = source.enemy_color == target.enemy_color;
+		let opposite_polarity = source.enemy_polarity != target.enemy_polarity;
+		match self {
+			BlastRule::SameColor => same_color,
+			BlastRule::OppositePolarity => opposite_polarity,
+			BlastRule::SameColorOppositePolarity => same_color && opposite_polarity,
+		}
+	}
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct CascadeRules {
+	/// How far a popping slime's blast reaches.
+	pub radius: f32,
+	/// A slime in range is ignited if any of these match.
+	pub rules: Vec<BlastRule>,
+	/// Points for every slime ignited by a blast, on top of the combo.
+	pub bonus: u32,
+}
+
+impl Default for CascadeRules {
+	fn default() -> Self {
+		Self {
+			radius: 60.0,
+			rules: vec![BlastRule::SameColor],
+			bonus: 1,
+		}
+	}
+}
+
+impl CascadeRules {
+	pub fn ignites(&self, source: &Enemy, target: &Enemy, distance: f32) -> bool {
+		distance <= self.radius
+			&& self.rules.iter().any(|rule| rule.matches(source, target))
+	}
+}
+
+/// The slimes among `candidates` ignited by `source` popping at `position`.
+pub fn ignited_by<'a>(
+	rules: &CascadeRules,
+	source: &Enemy,
+	position: Vec3,
+	candidates: impl IntoIterator<Item = (Entity, &'a Enemy, Vec3)>,
+) -> Vec<Entity> {
+	candidates
+		.into_iter()
+		.filter(|(_, target, target_position)| {
+			rules.ignites(source, target, position.distance(*target_position))
+		})
+		.map(|(entity, _, _)| entity)
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{EnemyColor, EnemyPolarity};
+
+	fn slime(color: usize, polarity: EnemyPolarity) -> Enemy {
+		Enemy {
+			enemy_color: EnemyColor(color),
+			enemy_polarity: polarity,
+			magnitude: 1,
+		}
+	}
+
+	#[test]
+	fn rules_compare_colour_and_polarity() {
+		let red = slime(0, EnemyPolarity::Positive);
+		let dark_red = slime(0, EnemyPolarity::Negative);
+		let blue = slime(2, EnemyPolarity::Positive);
+		let dark_blue = slime(2, EnemyPolarity::Negative);
+
+		assert!(BlastRule::SameColor.matches(&red, &dark_red));
+		assert!(!BlastRule::SameColor.matches(&red, &blue));
+		assert!(BlastRule::OppositePolarity.matches(&red, &dark_blue));
+		assert!(!BlastRule::OppositePolarity.matches(&red, &blue));
+		assert!(BlastRule::SameColorOppositePolarity.matches(&red, &dark_red));
+		assert!(!BlastRule::SameColorOppositePolarity.matches(&red, &dark_blue));
+	}
+
+	#[test]
+	fn ignition_needs_range_and_any_rule() {
+		let rules = CascadeRules {
+			radius: 50.0,
+			rules: vec![BlastRule::SameColor, BlastRule::OppositePolarity],
+			bonus: 1,
+		};
+		let red = slime(0, EnemyPolarity::Positive);
+		assert!(rules.ignites(&red, &slime(0, EnemyPolarity::Positive), 50.0));
+		assert!(rules.ignites(&red, &slime(1, EnemyPolarity::Negative), 10.0));
+		assert!(!rules.ignites(&red, &slime(1, EnemyPolarity::Positive), 10.0));
+		assert!(!rules.ignites(&red, &slime(0, EnemyPolarity::Positive), 50.1));
+	}
+
+	#[test]
+	fn only_candidates_in_range_are_ignited() {
+		let rules = CascadeRules::default();
+		let red = slime(0, EnemyPolarity::Positive);
+		let near = slime(0, EnemyPolarity::Negative);
+		let far = slime(0, EnemyPolarity::Negative);
+		let other = slime(1, EnemyPolarity::Negative);
+		let candidates = [
+			(Entity::from_raw(1), &near, Vec3::new(30.0, 0.0, 0.0)),
+			(Entity::from_raw(2), &far, Vec3::new(300.0, 0.0, 0.0)),
+			(Entity::from_raw(3), &other, Vec3::new(0.0, 10.0, 0.0)),
+		];
+		assert_eq!(
+			ignited_by(&rules, &red, Vec3::ZERO, candidates),
+			vec![Entity::from_raw(1)]
+		);
+	}
+}
@@ -0,0 +1,384 @@
+//! Ordered model of the chain the player builds out of slimes.
+
+use crate::events::SlimeUnchained;
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::iter;
+
+/// Marker for slimes that are currently a link in the [`ChainGraph`].
+#[derive(Component)]
+pub struct Chained;
+
+/// An ordered run of chained slimes.
+///
+/// Links are stored from the anchor outward: index 0 is held directly by the
+/// anchor and is always the most recently attached slime, so walking the links
+/// in order walks away from the player.
+#[derive(Clone, Debug, Default)]
+pub struct Chain {
+	links: Vec<Entity>,
+}
+
+impl Chain {
+	pub fn links(&self) -> &[Entity] {
+		&self.links
+	}
+
+	pub fn len(&self) -> usize {
+		self.links.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.links.is_empty()
+	}
+
+	pub fn contains(&self, entity: Entity) -> bool {
+		self.links.contains(&entity)
+	}
+
+	/// The link currently held by the anchor, i.e. the last slime attached.
+	pub fn newest(&self) -> Option<Entity> {
+		self.links.first().copied()
+	}
+
+	/// Attaches `entity` next to the anchor. Returns `false` if it is already a
+	/// link, since a slime can only appear once in a chain.
+	pub fn attach(&mut self, entity: Entity) -> bool {
+		if self.contains(entity) {
+			return false;
+		}
+		self.links.insert(0, entity);
+		true
+	}
+
+	/// Removes `entity` and joins its neighbours, so the chain stays contiguous.
+	/// Returns the index it was removed from.
+	pub fn remove(&mut self, entity: Entity) -> Option<usize> {
+		let index = self.links.iter().position(|link| *link == entity)?;
+		self.links.remove(index);
+		Some(index)
+	}
+
+	/// Detaches the link held by the anchor, undoing the last attach.
+	pub fn detach_newest(&mut self) -> Option<Entity> {
+		if self.links.is_empty() {
+			return None;
+		}
+		Some(self.links.remove(0))
+	}
+
+	/// Cuts the chain at `entity`, detaching it together with every link that
+	/// was attached after it. The older links stay held by the anchor.
+	pub fn cut_at(&mut self, entity: Entity) -> Vec<Entity> {
+		let Some(index) = self.links.iter().position(|link| *link == entity) else {
+			return Vec::new();
+		};
+		self.links.drain(..=index).collect()
+	}
+
+	/// Detaches the first `len` links, counted from the anchor, in detonation
+	/// order.
+	pub fn split_prefix(&mut self, len: usize) -> Vec<Entity> {
+		self.links.drain(..len.min(self.links.len())).collect()
+	}
+
+	/// Empties the chain, returning the links in detonation order.
+	pub fn take(&mut self) -> Vec<Entity> {
+		std::mem::take(&mut self.links)
+	}
+}
+
+/// Length of the longest prefix whose charges cancel out for every key, e.g.
+/// every slime colour. `None` entries, such as links whose slime is already
+/// gone, carry no charge.
+pub fn longest_balanced_prefix<K: Eq + Hash>(
+	charges: impl IntoIterator<Item = Option<(K, i32)>>,
+) -> usize {
+	let mut sums = HashMap::new();
+	let mut unbalanced = 0_usize;
+	let mut longest = 0;
+	for (i, charge) in charges.into_iter().enumerate() {
+		if let Some((key, charge)) = charge {
+			let sum = sums.entry(key).or_insert(0);
+			let was_balanced = *sum == 0;
+			*sum += charge;
+			match (was_balanced, *sum == 0) {
+				(true, false) => unbalanced += 1,
+				(false, true) => unbalanced -= 1,
+				_ => {}
+			}
+		}
+		if unbalanced == 0 {
+			longest = i + 1;
+		}
+	}
+	longest
+}
+
+/// Keeps the [`ChainGraph`] contiguous when a link loses [`Chained`], whether it
+/// was detached, despawned mid-chain or cleaned up with its game state. The
+/// balance HUD reads the graph every frame, so it follows on its own.
+pub fn on_remove_chained(
+	trigger: Trigger<OnRemove, Chained>,
+	chain_graph: Option<ResMut<ChainGraph>>,
+	mut unchained: EventWriter<SlimeUnchained>,
+) {
+	let Some(mut chain_graph) = chain_graph else {
+		return;
+	};
+	if chain_graph.remove(trigger.target()).is_some() {
+		unchained.write(SlimeUnchained {
+			entity: trigger.target(),
+		});
+	}
+}
+
+/// How many chains the player can hold at once.
+pub const MAX_CHAINS: usize = 3;
+
+/// Points a partial detonation loses unless the settings say otherwise.
+pub const DEFAULT_PARTIAL_DETONATION_PENALTY: u32 = 3;
+
+/// When enabled, Space on an unbalanced chain detonates its longest balanced
+/// prefix from the player and takes `penalty` points off that detonation.
+#[derive(Resource, Debug)]
+pub struct PartialDetonation {
+	pub enabled: bool,
+	pub penalty: u32,
+}
+
+impl Default for PartialDetonation {
+	fn default() -> Self {
+		Self {
+			enabled: false,
+			penalty: DEFAULT_PARTIAL_DETONATION_PENALTY,
+		}
+	}
+}
+
+/// Every chain held by the player. `apply_clicks` attaches links to the
+/// active chain and drawing, balance and detonation all read their order from
+/// here. There is always at least one chain, so there is always an active one.
+#[derive(Resource, Debug)]
+pub struct ChainGraph {
+	anchor: Entity,
+	chains: Vec<Chain>,
+	active: usize,
+}
+
+impl ChainGraph {
+	pub fn new(anchor: Entity) -> Self {
+		Self {
+			anchor,
+			chains: vec![Chain::default()],
+			active: 0,
+		}
+	}
+
+	pub fn anchor(&self) -> Entity {
+		self.anchor
+	}
+
+	pub fn active(&self) -> usize {
+		self.active
+	}
+
+	pub fn len(&self) -> usize {
+		self.chains.len()
+	}
+
+	pub fn chain(&self) -> &Chain {
+		&self.chains[self.active]
+	}
+
+	pub fn chain_mut(&mut self) -> &mut Chain {
+		&mut self.chains[self.active]
+	}
+
+	pub fn chain_at_mut(&mut self, index: usize) -> Option<&mut Chain> {
+		self.chains.get_mut(index)
+	}
+
+	/// Makes the next chain active. Cycling past a non-empty last chain starts
+	/// a fresh one, up to [`MAX_CHAINS`]. An empty chain that is cycled away from
+	/// is dropped.
+	pub fn cycle_active(&mut self) {
+		if self.active + 1 == self.chains.len()
+			&& !self.chain().is_empty()
+			&& self.chains.len() < MAX_CHAINS
+		{
+			self.chains.push(Chain::default());
+		}
+		let left_behind = self.active;
+		self.active = (self.active + 1) % self.chains.len();
+		self.drop_if_parked_and_empty(left_behind);
+	}
+
+	/// Drops the chain at `index` if it is empty and not the active one.
+	fn drop_if_parked_and_empty(&mut self, index: usize) {
+		if index == self.active || !self.chains[index].is_empty() {
+			return;
+		}
+		self.chains.remove(index);
+		if index < self.active {
+			self.active -= 1;
+		}
+	}
+
+	/// Removes `entity` from whichever chain holds it, dropping that chain if
+	/// it is parked and now empty.
+	pub fn remove(&mut self, entity: Entity) -> Option<usize> {
+		let (chain, index) = self
+			.chains
+			.iter_mut()
+			.enumerate()
+			.find_map(|(chain, links)| Some((chain, links.remove(entity)?)))?;
+		self.drop_if_parked_and_empty(chain);
+		Some(index)
+	}
+
+	/// Cuts whichever chain holds `entity`, see [`Chain::cut_at`], dropping that
+	/// chain if it is parked and now empty.
+	pub fn cut_at(&mut self, entity: Entity) -> Vec<Entity> {
+		let Some(index) = self.chains.iter().position(|chain| chain.contains(entity))
+		else {
+			return Vec::new();
+		};
+		let cut = self.chains[index].cut_at(entity);
+		self.drop_if_parked_and_empty(index);
+		cut
+	}
+
+	/// Every segment of the chain at `index` as `(inner, outer)` pairs,
+	/// starting with the segment between the anchor and the first link.
+	pub fn edges(&self, index: usize) -> impl Iterator<Item = (Entity, Entity)> + '_ {
+		let links = self.chains[index].links.iter().copied();
+		iter::once(self.anchor).chain(links.clone()).zip(links)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn entities(count: u32) -> Vec<Entity> {
+		(0..count).map(Entity::from_raw).collect()
+	}
+
+	#[test]
+	fn newest_link_is_detonated_first() {
+		let e = entities(3);
+		let mut chain = Chain::default();
+		for entity in &e {
+			assert!(chain.attach(*entity));
+		}
+		assert_eq!(chain.newest(), Some(e[2]));
+		assert_eq!(chain.take(), vec![e[2], e[1], e[0]]);
+		assert!(chain.is_empty());
+	}
+
+	#[test]
+	fn attaching_twice_is_rejected() {
+		let e = entities(2);
+		let mut chain = Chain::default();
+		chain.attach(e[0]);
+		chain.attach(e[1]);
+		assert!(!chain.attach(e[0]));
+		assert_eq!(chain.links(), &[e[1], e[0]]);
+	}
+
+	#[test]
+	fn cutting_detaches_everything_attached_after() {
+		let e = entities(4);
+		let mut chain = Chain::default();
+		for entity in &e {
+			chain.attach(*entity);
+		}
+		assert_eq!(chain.detach_newest(), Some(e[3]));
+		assert_eq!(chain.cut_at(e[1]), vec![e[2], e[1]]);
+		assert_eq!(chain.links(), &[e[0]]);
+		assert!(chain.cut_at(e[3]).is_empty());
+	}
+
+	#[test]
+	fn removing_a_link_joins_its_neighbours() {
+		let e = entities(4);
+		let mut graph = ChainGraph::new(e[0]);
+		for entity in &e[1..] {
+			graph.chain_mut().attach(*entity);
+		}
+		assert_eq!(graph.remove(e[2]), Some(1));
+		assert_eq!(graph.remove(e[2]), None);
+		assert_eq!(
+			graph.edges(0).collect::<Vec<_>>(),
+			vec![(e[0], e[3]), (e[3], e[1])]
+		);
+	}
+
+	#[test]
+	fn cycling_starts_a_new_chain_and_drops_empty_ones() {
+		let e = entities(3);
+		let mut graph = ChainGraph::new(e[0]);
+		graph.cycle_active();
+		assert_eq!((graph.len(), graph.active()), (1, 0));
+
+		graph.chain_mut().attach(e[1]);
+		graph.cycle_active();
+		assert_eq!((graph.len(), graph.active()), (2, 1));
+		graph.chain_mut().attach(e[2]);
+		// Cutting the parked chain empties it, so it is dropped straight away.
+		assert_eq!(graph.cut_at(e[1]), vec![e[1]]);
+		assert_eq!((graph.len(), graph.active()), (1, 0));
+
+		graph.cycle_active();
+		assert_eq!((graph.len(), graph.active()), (2, 1));
+		graph.cycle_active();
+		assert_eq!((graph.len(), graph.active()), (1, 0));
+		assert_eq!(graph.chain().links(), &[e[2]]);
+	}
+
+	#[test]
+	fn removing_the_last_link_of_a_parked_chain_drops_it() {
+		let e = entities(4);
+		let mut graph = ChainGraph::new(e[0]);
+		graph.chain_mut().attach(e[1]);
+		graph.cycle_active();
+		graph.chain_mut().attach(e[2]);
+		graph.cycle_active();
+		graph.chain_mut().attach(e[3]);
+		assert_eq!((graph.len(), graph.active()), (3, 2));
+
+		assert_eq!(graph.remove(e[1]), Some(0));
+		assert_eq!((graph.len(), graph.active()), (2, 1));
+		assert_eq!(graph.chain().links(), &[e[3]]);
+		// The active chain stays put even when it runs out of links.
+		assert_eq!(graph.remove(e[3]), Some(0));
+		assert_eq!((graph.len(), graph.active()), (2, 1));
+		assert!(graph.chain().is_empty());
+	}
+
+	#[test]
+	fn balanced_prefix_stops_at_the_last_zero_sum() {
+		let charges = [
+			Some(("red", 1)),
+			Some(("red", -1)),
+			None,
+			Some(("blue", 1)),
+			Some(("red", 1)),
+			Some(("blue", -1)),
+			Some(("red", -1)),
+			Some(("blue", 1)),
+		];
+		assert_eq!(longest_balanced_prefix(charges), 7);
+		assert_eq!(longest_balanced_prefix([Some(("red", 1))]), 0);
+
+		let e = entities(3);
+		let mut chain = Chain::default();
+		for entity in &e {
+			chain.attach(*entity);
+		}
+		assert_eq!(chain.split_prefix(2), vec![e[2], e[1]]);
+		assert_eq!(chain.split_prefix(5), vec![e[0]]);
+	}
+}
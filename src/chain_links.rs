@@ -0,0 +1,190 @@
+//! Sprites for the chain segments. Each edge of the [`ChainGraph`] keeps one
+//! tiled sprite that is moved and stretched every frame; sprites are only
+//! spawned when there are more edges than ever before, and spare ones are
+//! hidden and handed to the next new edge.
+
+use crate::ChainAsset;
+use crate::chain::ChainGraph;
+use crate::menus::GameState;
+use bevy::prelude::*;
+use bevy::sprite::SpriteImageMode;
+use std::collections::HashSet;
+use std::f32::consts::PI;
+
+/// Size of one link in the chain texture.
+const CHAIN_SIZE: f32 = 12.0;
+
+/// The edge a pooled chain sprite is currently drawing.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChainLinkSprite {
+	inner: Entity,
+	outer: Entity,
+}
+
+pub fn draw_chains(
+	mut commands: Commands,
+	chain_graph: Res<ChainGraph>,
+	positions: Query<&GlobalTransform, Without<ChainLinkSprite>>,
+	mut sprites: Query<(
+		Entity,
+		&mut ChainLinkSprite,
+		&mut Sprite,
+		&mut Transform,
+		&mut Visibility,
+	)>,
+	chain_asset: Res<ChainAsset>,
+) {
+	let mut edges = Vec::new();
+	for index in 0..chain_graph.len() {
+		// Parked chains are drawn faded so the active one stands out.
+		let color = if index == chain_graph.active() {
+			Color::WHITE
+		} else {
+			Color::WHITE.with_alpha(0.4)
+		};
+		for (inner, outer) in chain_graph.edges(index) {
+			let (Ok(inner_position), Ok(outer_position)) =
+				(positions.get(inner), positions.get(outer))
+			else {
+				continue;
+			};
+			let (inner_position, outer_position) =
+				(inner_position.translation(), outer_position.translation());
+			let size = Vec2::new(
+				CHAIN_SIZE,
+				(inner_position.distance(outer_position) - CHAIN_SIZE).max(0.0),
+			);
+			edges.push((
+				ChainLinkSprite { inner, outer },
+				segment_transform(inner_position, outer_position),
+				size,
+				color,
+			));
+		}
+	}
+
+	// Sprites already drawing a live edge keep it; the rest are free to reuse.
+	let mut drawn = HashSet::new();
+	let mut spare = Vec::new();
+	for (entity, link, mut sprite, mut transform, _) in sprites.iter_mut() {
+		let edge = edges.iter().find(|(edge, ..)| *edge == *link);
+		match edge {
+			Some((edge, edge_transform, size, color)) if drawn.insert(*edge) => {
+				sprite.custom_size = Some(*size);
+				sprite.color = *color;
+				*transform = *edge_transform;
+			}
+			_ => spare.push(entity),
+		}
+	}
+
+	for (edge, transform, size, color) in edges {
+		if drawn.contains(&edge) {
+			continue;
+		}
+		if let Some((_, mut link, mut sprite, mut old_transform, mut visibility)) =
+			spare.pop().and_then(|entity| sprites.get_mut(entity).ok())
+		{
+			*link = edge;
+			sprite.custom_size = Some(size);
+			sprite.color = color;
+			*old_transform = transform;
+			*visibility = Visibility::Inherited;
+			continue;
+		}
+		commands.spawn((
+			edge,
+			Sprite {
+				image: chain_asset.0.clone(),
+				color,
+				custom_size: Some(size),
+				image_mode: SpriteImageMode::Tiled {
+					tile_x: false,
+					tile_y: true,
+					stretch_value: 1.0,
+				},
+				..default()
+			},
+			transform,
+			StateScoped(GameState::Game),
+		));
+	}
+	for entity in spare {
+		if let Ok((.., mut visibility)) = sprites.get_mut(entity) {
+			*visibility = Visibility::Hidden;
+		}
+	}
+}
+
+/// Centres a segment between its ends, rotated so the texture's y axis runs
+/// along it.
+fn segment_transform(inner: Vec3, outer: Vec3) -> Transform {
+	let delta = outer - inner;
+	let angle = delta.y.atan2(delta.x);
+	let mut center = inner.lerp(outer, 0.5);
+	center.z = 1.0;
+	Transform::from_translation(center)
+		.with_rotation(Quat::from_rotation_z(PI / 2.0 + angle))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn link_sprites(app: &mut App) -> HashSet<Entity> {
+		app.world_mut()
+			.query_filtered::<Entity, With<ChainLinkSprite>>()
+			.iter(app.world())
+			.collect()
+	}
+
+	#[test]
+	fn link_sprites_are_only_spawned_for_new_edges() {
+		let mut app = App::new();
+		app.insert_resource(ChainAsset(Handle::default()));
+		app.add_systems(Update, draw_chains);
+		let player = app
+			.world_mut()
+			.spawn(GlobalTransform::from_xyz(0.0, 0.0, 0.0))
+			.id();
+		app.insert_resource(ChainGraph::new(player));
+
+		let mut previous = HashSet::new();
+		for length in 1..=50 {
+			let slime = app
+				.world_mut()
+				.spawn(GlobalTransform::from_xyz(length as f32 * 40.0, 0.0, 0.0))
+				.id();
+			app.world_mut()
+				.resource_mut::<ChainGraph>()
+				.chain_mut()
+				.attach(slime);
+
+			app.update();
+			let current = link_sprites(&mut app);
+			assert_eq!(current.len(), length);
+			assert_eq!(current.difference(&previous).count(), 1);
+			assert!(previous.is_subset(&current));
+
+			// A frame where the chain doesn't change spawns nothing.
+			app.update();
+			assert_eq!(link_sprites(&mut app), current);
+			previous = current;
+		}
+
+		// Shortening the chain hides sprites for reuse instead of despawning them.
+		let newest = app
+			.world_mut()
+			.resource_mut::<ChainGraph>()
+			.chain_mut()
+			.split_prefix(10);
+		app.update();
+		assert_eq!(link_sprites(&mut app), previous);
+		app.world_mut()
+			.resource_mut::<ChainGraph>()
+			.chain_mut()
+			.attach(newest[0]);
+		app.update();
+		assert_eq!(link_sprites(&mut app), previous);
+	}
+}
@@ -0,0 +1,91 @@
+//! The daily challenge: one seed per UTC day and the same upgrades for
+//! everyone, with scores filed apart from regular runs.
+
+use crate::chain::DEFAULT_PARTIAL_DETONATION_PENALTY;
+use crate::game_mode::GameMode;
+use crate::game_rng::RunSeed;
+use crate::menus::GameState;
+use crate::replay::Conditions;
+use crate::slime_palette::DEFAULT_COLORS;
+use bevy::prelude::*;
+use chrono::{Datelike, NaiveDate, Utc};
+
+pub struct DailyPlugin;
+impl Plugin for DailyPlugin {
+	fn build(&self, app: &mut App) {
+		app.add_systems(OnExit(GameState::Game), end_daily);
+	}
+}
+
+/// The upgrades and settings every daily run is played with, whatever has been
+/// bought in the shop or picked in the settings. The spawn view is fixed too, so players with different
+/// window sizes still get the same spawns.
+pub const DAILY_CONDITIONS: Conditions = Conditions {
+	chain_radius_level: 1,
+	slime_slowness_level: 1,
+	partial_detonation: false,
+	partial_detonation_penalty: DEFAULT_PARTIAL_DETONATION_PENALTY,
+	mode: GameMode::Endless,
+	view: UVec2::new(1280, 720),
+	colors: DEFAULT_COLORS as u8,
+};
+
+/// The challenge for one UTC day.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Daily(pub NaiveDate);
+
+impl Daily {
+	pub fn today() -> Self {
+		Self(Utc::now().date_naive())
+	}
+
+	/// The date written out as digits, so 2026-10-18 is seed 20261018.
+	pub fn seed(&self) -> u64 {
+		let date = self.0;
+		date.year() as u64 * 10_000 + date.month() as u64 * 100 + date.day() as u64
+	}
+
+	/// The leaderboard category this day's scores are sent under.
+	pub fn category(&self) -> String {
+		format!("daily-{}", self.0.format("%Y-%m-%d"))
+	}
+}
+
+/// Present while a daily run is being played, with the conditions to put back
+/// once it ends.
+#[derive(Resource, Debug)]
+pub struct DailyRun {
+	pub daily: Daily,
+	restore: Conditions,
+}
+
+/// Starts a run of `daily`.
+pub fn begin_daily(world: &mut World, daily: Daily) {
+	let restore = Conditions::of(world);
+	DAILY_CONDITIONS.apply(world);
+	world.resource_mut::<RunSeed>().0 = Some(daily.seed());
+	world.insert_resource(DailyRun { daily, restore });
+	world
+		.resource_mut::<NextState<GameState>>()
+		.set(GameState::Game);
+}
+
+pub fn end_daily(world: &mut World) {
+	if let Some(run) = world.remove_resource::<DailyRun>() {
+		run.restore.apply(world);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn each_day_has_its_own_seed_and_category() {
+		let day = Daily(NaiveDate::from_ymd_opt(2026, 10, 18).unwrap());
+		let next = Daily(NaiveDate::from_ymd_opt(2026, 10, 19).unwrap());
+		assert_eq!(day.seed(), 20261018);
+		assert_ne!(day.seed(), next.seed());
+		assert_eq!(day.category(), "daily-2026-10-18");
+	}
+}
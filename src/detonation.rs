@@ -0,0 +1,456 @@
+//! The chain reaction itself. A [`Detonation`] pops its slimes one at a time
+//! on simulation ticks, so it stops while the game is paused and can be
+//! inspected (or despawned to cancel it) like any other entity. Links it hasn't
+//! popped yet when it goes away are released from the chain.
+
+use crate::archetype::{Archetype, split};
+use crate::boss::Boss;
+use crate::cascade::{CascadeRules, ignited_by};
+use crate::chain::{ChainGraph, Chained};
+use crate::enemy::SpawnEnemy;
+use crate::events::{
+	ActionRejected, BossHit, ChainDetonationFinished, ChainDetonationStarted,
+	SlimePopped, SlimeSquished,
+};
+use crate::explosion::FireParticleMaterial;
+use crate::menus::GameState;
+use crate::slime_palette::SlimePalette;
+use crate::tick_input::SlimeId;
+use crate::{Enemy, Simulation, StartChainReaction, release_links};
+use bevy::prelude::EaseFunction::BounceOut;
+use bevy::prelude::*;
+use bevy_enoki::prelude::{MultiCurve, OneShot, ParticleSpawnerState, Rval};
+use bevy_enoki::{Particle2dEffect, ParticleEffectHandle, ParticleSpawner};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// How long a slime squishes before it pops.
+const POP_DELAY: Duration = Duration::from_millis(83);
+
+pub struct DetonationPlugin;
+impl Plugin for DetonationPlugin {
+	fn build(&self, app: &mut App) {
+		app.add_systems(
+			FixedUpdate,
+			(start_chain_reaction, step_detonations)
+				.chain()
+				.in_set(Simulation::Detonation),
+		);
+		app.add_observer(release_unpopped_links);
+	}
+}
+
+/// Particles for squishing slimes.
+pub struct DetonationVisualsPlugin;
+impl Plugin for DetonationVisualsPlugin {
+	fn build(&self, app: &mut App) {
+		app.add_systems(Update, spawn_pop_particles);
+	}
+}
+
+/// What a [`Detonation`] wants done next.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DetonationStep {
+	/// The `index`th slime starts to squish.
+	Squish { entity: Entity, index: u32 },
+	/// The slime pops. Report what it ignited with [`Detonation::popped`].
+	Pop { entity: Entity },
+	/// Every slime has popped and `score` points were earned.
+	Finished { score: u32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stage {
+	Fuse,
+	Squishing(Entity),
+	Done,
+}
+
+#[derive(Component, Debug)]
+pub struct Detonation {
+	queue: VecDeque<Entity>,
+	lit: Vec<Entity>,
+	index: u32,
+	combo: u32,
+	cascades: u32,
+	penalty: u32,
+	stage: Stage,
+	wait: Duration,
+	budget: Duration,
+}
+
+impl Detonation {
+	pub fn new(links: Vec<Entity>, penalty: u32) -> Self {
+		Self {
+			lit: links.clone(),
+			queue: links.into(),
+			index: 0,
+			combo: 1,
+			cascades: 0,
+			penalty,
+			stage: Stage::Fuse,
+			wait: fuse(0),
+			budget: Duration::ZERO,
+		}
+	}
+
+	/// The combo the next slime to pop will show.
+	pub fn combo(&self) -> u32 {
+		self.combo
+	}
+
+	/// Slimes still waiting to pop, cascaded ones included.
+	pub fn remaining(&self) -> usize {
+		self.queue.len()
+	}
+
+	/// Every slime this detonation has queued, popped or not.
+	pub fn lit(&self) -> &[Entity] {
+		&self.lit
+	}
+
+	pub fn is_finished(&self) -> bool {
+		self.stage == Stage::Done
+	}
+
+	pub fn tick(&mut self, delta: Duration) {
+		self.budget += delta;
+	}
+
+	/// The next step that is due, if any. Call until it returns `None`.
+	pub fn next_step(&mut self) -> Option<DetonationStep> {
+		if self.stage == Stage::Done || self.budget < self.wait {
+			return None;
+		}
+		self.budget -= self.wait;
+		match self.stage {
+			Stage::Fuse => match self.queue.front() {
+				Some(&entity) => {
+					self.stage = Stage::Squishing(entity);
+					self.wait = POP_DELAY;
+					Some(DetonationStep::Squish {
+						entity,
+						index: self.index,
+					})
+				}
+				None => {
+					self.stage = Stage::Done;
+					Some(DetonationStep::Finished {
+						score: (self.combo + self.cascades)
+							.saturating_sub(self.penalty),
+					})
+				}
+			},
+			Stage::Squishing(entity) => {
+				self.queue.pop_front();
+				self.index += 1;
+				self.stage = Stage::Fuse;
+				self.wait = fuse(self.index);
+				Some(DetonationStep::Pop { entity })
+			}
+			Stage::Done => None,
+		}
+	}
+
+	/// Records that a slime actually popped, queueing the slimes its blast
+	/// ignited and scoring `bonus` for each of them.
+	pub fn popped(&mut self, ignited: Vec<Entity>, bonus: u32) {
+		self.combo += 2;
+		self.cascades += bonus * ignited.len() as u32;
+		self.lit.extend(&ignited);
+		self.queue.extend(ignited);
+	}
+}
+
+/// The pause before the `index`th slime squishes; the reaction speeds up as it
+/// goes.
+fn fuse(index: u32) -> Duration {
+	Duration::from_secs_f32((0.5 / 1.2_f32.powf(index as f32)).max(0.05))
+}
+
+fn start_chain_reaction(
+	mut event_reader: EventReader<StartChainReaction>,
+	mut chain_graph: ResMut<ChainGraph>,
+	mut commands: Commands,
+	mut detonation_started: EventWriter<ChainDetonationStarted>,
+	mut action_rejected: EventWriter<ActionRejected>,
+) {
+	let Some(StartChainReaction {
+		chain,
+		links,
+		penalty,
+	}) = event_reader.read().last().copied()
+	else {
+		return;
+	};
+	let Some(links) = chain_graph
+		.chain_at_mut(chain)
+		.map(|chain| chain.split_prefix(links))
+	else {
+		return;
+	};
+	if links.is_empty() {
+		action_rejected.write(ActionRejected);
+		return; // no chained entities at all lol
+	}
+	detonation_started.write(ChainDetonationStarted {
+		chain,
+		links: links.len(),
+	});
+	commands.spawn((
+		Detonation::new(links, penalty),
+		StateScoped(GameState::Game),
+	));
+}
+
+/// A detonation that goes away before it finishes, whether cancelled or
+/// cleaned up with the run, lets go of the links it never popped. They have
+/// already been split off the [`ChainGraph`], so nothing else would.
+fn release_unpopped_links(
+	trigger: Trigger<OnRemove, Detonation>,
+	detonations: Query<&Detonation>,
+	chained: Query<(), With<Chained>>,
+	mut commands: Commands,
+) {
+	let Ok(detonation) = detonations.get(trigger.target()) else {
+		return;
+	};
+	let unpopped = detonation.queue.iter().copied();
+	release_links(&mut commands, unpopped.filter(|link| chained.contains(*link)));
+}
+
+pub fn step_detonations(
+	mut commands: Commands,
+	time: Res<Time>,
+	mut detonations: Query<(Entity, &mut Detonation)>,
+	enemies: Query<(&Enemy, &Archetype, &Transform, Has<Chained>)>,
+	unchained: Query<
+		(Entity, &SlimeId, &Enemy, &Transform),
+		(Without<Chained>, Without<Boss>),
+	>,
+	rules: Res<CascadeRules>,
+	mut slime_squished: EventWriter<SlimeSquished>,
+	mut slime_popped: EventWriter<SlimePopped>,
+	mut detonation_finished: EventWriter<ChainDetonationFinished>,
+	mut spawn_enemy: EventWriter<SpawnEnemy>,
+	mut boss_hit: EventWriter<BossHit>,
+) {
+	for (detonation_entity, mut detonation) in detonations.iter_mut() {
+		detonation.tick(time.delta());
+		while let Some(step) = detonation.next_step() {
+			match step {
+				DetonationStep::Squish { entity, .. } => {
+					let Ok((enemy, _, transform, _)) = enemies.get(entity) else {
+						continue;
+					};
+					slime_squished.write(SlimeSquished {
+						entity,
+						enemy: *enemy,
+						position: transform.translation,
+					});
+				}
+				DetonationStep::Pop { entity } => {
+					let Ok((enemy, archetype, transform, chained)) = enemies.get(entity)
+					else {
+						continue;
+					};
+					// Unchained slimes caught in the blast pop later in this same
+					// reaction, in spawn order so replays pop them the same way.
+					let mut candidates: Vec<_> = unchained
+						.iter()
+						.filter(|(e, ..)| !detonation.lit().contains(e))
+						.collect();
+					candidates.sort_unstable_by_key(|(_, id, ..)| **id);
+					let ignited = ignited_by(
+						&rules,
+						enemy,
+						transform.translation,
+						candidates
+							.into_iter()
+							.map(|(e, _, enemy, t)| (e, enemy, t.translation)),
+					);
+					slime_popped.write(SlimePopped {
+						entity,
+						enemy: *enemy,
+						position: transform.translation,
+						combo_index: detonation.combo(),
+					});
+					match archetype {
+						// Armour takes the blast, leaving a plain slime behind. Only
+						// links have a chain to leave; cascaded slimes never joined.
+						Archetype::Armored => {
+							if chained {
+								release_links(&mut commands, [entity]);
+							}
+							commands.entity(entity).insert(Archetype::Plain);
+						}
+						Archetype::Splitter => {
+							let position = transform.translation.xy();
+							spawn_enemy.write_batch(split(enemy, position));
+							commands.entity(entity).despawn();
+						}
+						// Only links reach here, since blasts never ignite bosses.
+						Archetype::Boss => {
+							boss_hit.write(BossHit { entity });
+						}
+						_ => commands.entity(entity).despawn(),
+					}
+					detonation.popped(ignited, rules.bonus);
+				}
+				DetonationStep::Finished { score } => {
+					detonation_finished.write(ChainDetonationFinished { score });
+					commands.entity(detonation_entity).despawn();
+				}
+			}
+		}
+	}
+}
+
+fn spawn_pop_particles(
+	mut commands: Commands,
+	mut slime_squished: EventReader<SlimeSquished>,
+	palette: Res<SlimePalette>,
+	asset_server: Res<AssetServer>,
+	mut materials: ResMut<Assets<FireParticleMaterial>>,
+) {
+	for SlimeSquished {
+		enemy, position, ..
+	} in slime_squished.read()
+	{
+		let material_handle = materials.add(FireParticleMaterial {
+			texture: asset_server.load("images/noise.png"),
+		});
+		let effect = asset_server.add(pop_particles(palette.color_of(enemy)));
+		commands.spawn((
+			ParticleEffectHandle(effect),
+			OneShot::Despawn,
+			ParticleSpawnerState::default(),
+			ParticleSpawner(material_handle),
+			Transform::from_translation(*position),
+		));
+	}
+}
+
+fn pop_particles(color: Color) -> Particle2dEffect {
+	Particle2dEffect {
+		spawn_rate: 0.0,
+		spawn_amount: 50,
+		emission_shape: Default::default(), // Equivalent to Point
+		lifetime: Rval::new(0.3, 0.5),
+		linear_speed: Some(Rval::new(25.0, 25.0)),
+		linear_acceleration: Some(Rval::new(-1.0, -1.5)),
+		direction: Some(Rval::new(Vec2::new(0.1, 0.1), 0.314)),
+		angular_speed: Some(Rval::new(200.0, 300.0)),
+		angular_acceleration: Some(Rval::new(-300.0, -200.0)),
+		gravity_direction: None,
+		gravity_speed: None,
+		scale: Some(Rval::new(0.0, 100.0)),
+		linear_damp: Some(Rval::new(0.8, 20.0)),
+		angular_damp: Some(Rval::new(0.0, 10.0)),
+		scale_curve: Some(MultiCurve {
+			points: vec![(10.0, 0.0, None), (30.0, 1.0, Some(BounceOut))],
+		}),
+		color: Some(color.into()),
+		color_curve: None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::EnemyClickable;
+	use crate::events::SlimeUnchained;
+
+	#[test]
+	fn slimes_pop_in_order_as_time_advances() {
+		let e: Vec<Entity> = (0..3).map(Entity::from_raw).collect();
+		let mut detonation = Detonation::new(vec![e[0], e[1]], 0);
+		assert_eq!(detonation.next_step(), None);
+
+		detonation.tick(Duration::from_millis(499));
+		assert_eq!(detonation.next_step(), None);
+		detonation.tick(Duration::from_millis(1));
+		assert_eq!(
+			detonation.next_step(),
+			Some(DetonationStep::Squish {
+				entity: e[0],
+				index: 0
+			})
+		);
+		assert_eq!(detonation.next_step(), None);
+		detonation.tick(POP_DELAY);
+		assert_eq!(detonation.next_step(), Some(DetonationStep::Pop { entity: e[0] }));
+		detonation.popped(vec![e[2]], 1);
+		assert_eq!(detonation.remaining(), 2);
+
+		// A long frame catches up on every step that became due.
+		detonation.tick(Duration::from_secs(5));
+		let mut steps = Vec::new();
+		while let Some(step) = detonation.next_step() {
+			if let DetonationStep::Pop { .. } = step {
+				detonation.popped(Vec::new(), 1);
+			}
+			steps.push(step);
+		}
+		assert_eq!(
+			steps,
+			vec![
+				DetonationStep::Squish {
+					entity: e[1],
+					index: 1
+				},
+				DetonationStep::Pop { entity: e[1] },
+				DetonationStep::Squish {
+					entity: e[2],
+					index: 2
+				},
+				DetonationStep::Pop { entity: e[2] },
+				// Three pops take the combo from 1 to 7, plus one cascade.
+				DetonationStep::Finished { score: 8 },
+			]
+		);
+		assert!(detonation.is_finished());
+	}
+
+	#[test]
+	fn cancelling_releases_the_links_not_yet_popped() {
+		let mut world = World::new();
+		world.init_resource::<Events<SlimeUnchained>>();
+		world.add_observer(release_unpopped_links);
+		let e: Vec<Entity> = (0..3).map(|_| world.spawn(Chained).id()).collect();
+		let mut detonation = Detonation::new(e.clone(), 0);
+		detonation.tick(Duration::from_secs(5));
+		assert!(matches!(
+			detonation.next_step(),
+			Some(DetonationStep::Squish { .. })
+		));
+		assert_eq!(detonation.next_step(), Some(DetonationStep::Pop { entity: e[0] }));
+		detonation.popped(Vec::new(), 0);
+
+		let detonation = world.spawn(detonation).id();
+		world.despawn(detonation);
+		world.flush();
+		assert!(world.get::<Chained>(e[0]).is_some());
+		for link in &e[1..] {
+			assert!(world.get::<Chained>(*link).is_none());
+			assert!(world.get::<EnemyClickable>(*link).is_some());
+		}
+		assert_eq!(world.resource::<Events<SlimeUnchained>>().len(), 2);
+	}
+
+	#[test]
+	fn penalty_comes_off_the_score() {
+		let mut detonation = Detonation::new(vec![Entity::from_raw(0)], 5);
+		detonation.tick(Duration::from_secs(5));
+		assert!(matches!(
+			detonation.next_step(),
+			Some(DetonationStep::Squish { .. })
+		));
+		assert!(matches!(detonation.next_step(), Some(DetonationStep::Pop { .. })));
+		detonation.popped(Vec::new(), 0);
+		assert_eq!(
+			detonation.next_step(),
+			Some(DetonationStep::Finished { score: 0 })
+		);
+	}
+}
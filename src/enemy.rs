@@ -1,12 +1,19 @@
+use crate::archetype::Archetype;
+use crate::daily::end_daily;
+use crate::game_mode::{GameMode, RunClock};
+use crate::game_rng::GameRng;
+use crate::interpolation::Interpolated;
 use crate::menus::GameState;
+use crate::replay::Playback;
+use crate::slime_palette::SlimePalette;
+use crate::spatial::SpatialIndex;
+use crate::waves::{CurrentWave, WaveSettings, sample_wave};
 use crate::{
-	Enemy, MAP_RADI, MaxInternalVelocity, Player, Velocity, on_click_enemy,
-	on_mouse_no_longer_over_enemy, on_mouse_over_enemy,
+	Enemy, MAP_RADI, MaxInternalVelocity, Player, Simulation, Velocity, on_click_enemy,
+	on_mouse_no_longer_over_enemy, on_mouse_over_enemy, on_right_click_enemy,
 };
 use bevy::prelude::*;
-use bevy::tasks::futures_lite::StreamExt;
-use bevy_defer::{AsyncAccess, AsyncCommandsExtension, AsyncWorld};
-use random_number::random;
+use rand::Rng;
 use std::time::Duration;
 
 pub struct EnemyPlugin;
@@ -14,92 +21,414 @@ pub struct EnemyPlugin;
 impl Plugin for EnemyPlugin {
 	fn build(&self, app: &mut App) {
 		app.add_event::<SpawnEnemy>();
-		app.add_systems(Startup, spawn_enemy_clusters);
-		app.add_systems(Update, handle_spawn_enemy.run_if(in_state(GameState::Game)));
+		app.init_resource::<ClusterTimer>();
+		app.init_resource::<PendingClusters>();
+		app.init_resource::<SpawnView>();
+		app.add_systems(OnEnter(GameState::Game), reset_cluster_timer);
+		app.add_systems(
+			FixedUpdate,
+			(
+				sample_wave,
+				spawn_enemy_clusters,
+				hatch_clusters,
+				handle_spawn_enemy,
+			)
+				.chain()
+				.in_set(Simulation::Spawn),
+		);
 	}
 }
 
-fn spawn_enemy_clusters(mut commands: Commands) {
-	commands.spawn_task(move || async move {
-		loop {
-			AsyncWorld.in_state(GameState::Game).await;
-			AsyncWorld.sleep(Duration::new(random!(1..2), 0)).await;
-			if !AsyncWorld.resource_scope(|state: Mut<State<GameState>>| {
-				state.get() == &GameState::Game
-			}) {
-				continue;
-			}
-			let mut enemy_types = vec![Enemy::random()];
-			while random!(0.0..1.0) > 0.4 {
-				enemy_types.push(Enemy::random());
-			}
-			let mut starting_position =
-				Vec2::new(random!(0.01..1.0), random!(0.01..1.0));
-			if starting_position.x < 0.5 {
-				starting_position.x = -starting_position.x;
-			}
-			if starting_position.y < 0.5 {
-				starting_position.y = -starting_position.y;
-			}
-			let starting_position = starting_position.try_normalize().unwrap(); // This should always succeed
-			let t = AsyncWorld
-				.query_filtered::<&mut Transform, With<Player>>()
-				.single();
-			if !AsyncWorld.resource_scope(|state: Mut<State<GameState>>| {
-				state.get() == &GameState::Game
-			}) {
-				continue;
-			}
-			let t = t.get_mut(|a| a.clone())?;
-			let starting_position =
-				starting_position * Vec2::splat(1000.0) + t.translation.xy();
-			for _ in 0..((random!(3..10) + random!(0..10)) / 3) {
-				let enemy = enemy_types
-					.get(random!(0..enemy_types.len()) as usize)
-					.unwrap()
-					.clone();
-				AsyncWorld.send_event(SpawnEnemy {
-					position: starting_position
-						+ Vec2::new(random!(-50.0..50.0), random!(-50.0..50.0)),
-					enemy,
-				})?;
+/// Slime sprites, hover highlights and spawn telegraphs.
+pub struct EnemyVisualsPlugin;
+impl Plugin for EnemyVisualsPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<MeasuredView>();
+		app.add_observer(dress_enemy);
+		app.add_observer(dress_telegraph);
+		app.add_systems(
+			Update,
+			(measure_view, pulse_telegraphs).run_if(in_state(GameState::Game)),
+		);
+		app.add_systems(
+			OnExit(GameState::Game),
+			carry_view_to_next_run
+				.run_if(in_state(Playback::Live))
+				.after(end_daily),
+		);
+	}
+}
+
+/// How long a cluster's spawn point is shown before its slimes appear.
+const TELEGRAPH_TIME: f32 = 0.75;
+/// Slimes are scattered this far around their cluster's centre.
+const CLUSTER_RADIUS: f32 = 50.0;
+/// Clusters never spawn closer than this to the player, even with a tiny view.
+const MIN_PLAYER_DISTANCE: f32 = 700.0;
+/// Nor closer than this to slimes already out or clusters about to hatch.
+const MIN_CLUSTER_DISTANCE: f32 = 300.0;
+/// How far past the edge of the view clusters may land.
+const SPAWN_BAND: f32 = 900.0;
+/// Spawn points tried before a cluster is skipped.
+const SPAWN_ATTEMPTS: usize = 16;
+
+/// The size of the area the camera shows, in world units. Clusters spawn
+/// outside it. It is measured by the presentation and only changes between
+/// runs, so a run and its replay place clusters alike.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpawnView(pub UVec2);
+
+impl Default for SpawnView {
+	fn default() -> Self {
+		Self(UVec2::new(1280, 720))
+	}
+}
+
+/// A cluster whose spawn point is being telegraphed.
+struct PendingCluster {
+	center: Vec2,
+	telegraph: Entity,
+	timer: Timer,
+	slimes: Vec<SpawnEnemy>,
+}
+
+/// In the order they were picked, so they hatch in a fixed order.
+#[derive(Resource, Default)]
+struct PendingClusters(Vec<PendingCluster>);
+
+/// Marks where a cluster is about to appear. `progress` goes from 0 to 1 as
+/// it gets closer to hatching.
+#[derive(Component, Default)]
+pub struct SpawnTelegraph {
+	pub progress: f32,
+}
+
+/// Counts down to the next cluster in simulation time.
+#[derive(Resource)]
+struct ClusterTimer(Timer);
+
+impl Default for ClusterTimer {
+	fn default() -> Self {
+		Self(Timer::from_seconds(1.0, TimerMode::Repeating))
+	}
+}
+
+fn reset_cluster_timer(
+	mut cluster_timer: ResMut<ClusterTimer>,
+	mut pending: ResMut<PendingClusters>,
+) {
+	*cluster_timer = ClusterTimer::default();
+	pending.0.clear();
+}
+
+fn spawn_enemy_clusters(
+	time: Res<Time>,
+	mode: Res<GameMode>,
+	run_clock: Res<RunClock>,
+	current_wave: Res<CurrentWave>,
+	mut cluster_timer: ResMut<ClusterTimer>,
+	player: Single<&Transform, With<Player>>,
+	palette: Res<SlimePalette>,
+	spawn_view: Res<SpawnView>,
+	spatial_index: Res<SpatialIndex>,
+	mut pending: ResMut<PendingClusters>,
+	mut commands: Commands,
+	mut rng: ResMut<GameRng>,
+) {
+	let Some(rate) = mode.spawn_rate(run_clock.0) else {
+		return;
+	};
+	let wave = &current_wave.0;
+	cluster_timer
+		.0
+		.set_duration(Duration::from_secs_f32(wave.interval / rate));
+	if !cluster_timer.0.tick(time.delta()).just_finished() {
+		return;
+	}
+	let rng = rng.gameplay();
+	let taken: Vec<Vec2> = spatial_index
+		.iter()
+		.map(|(_, position)| position)
+		.chain(pending.0.iter().map(|cluster| cluster.center))
+		.collect();
+	let Some(center) =
+		pick_spawn_point(rng, player.translation.xy(), spawn_view.0, &taken)
+	else {
+		return;
+	};
+	let telegraph = commands
+		.spawn((
+			Transform::from_translation(center.extend(-1.0)),
+			SpawnTelegraph::default(),
+			StateScoped(GameState::Game),
+		))
+		.id();
+	pending.0.push(PendingCluster {
+		center,
+		telegraph,
+		timer: Timer::from_seconds(TELEGRAPH_TIME, TimerMode::Once),
+		slimes: random_cluster(rng, &palette, wave, center),
+	});
+}
+
+/// Somewhere out of view of a player at `player`, inside the map and clear of
+/// everything in `taken`, or `None` if nowhere nearby fits.
+pub fn pick_spawn_point(
+	rng: &mut impl Rng,
+	player: Vec2,
+	view: UVec2,
+	taken: &[Vec2],
+) -> Option<Vec2> {
+	let half_view = view.as_vec2() / 2.0 + CLUSTER_RADIUS;
+	let nearest = half_view.length().max(MIN_PLAYER_DISTANCE);
+	let map = Rect::from_center_half_size(Vec2::ZERO, MAP_RADI - CLUSTER_RADIUS);
+	let view = Rect::from_center_half_size(player, half_view);
+	(0..SPAWN_ATTEMPTS)
+		.map(|_| {
+			let direction = Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU));
+			player + direction * rng.gen_range(nearest..nearest + SPAWN_BAND)
+		})
+		.find(|&point| {
+			map.contains(point)
+				&& !view.contains(point)
+				&& taken
+					.iter()
+					.all(|other| other.distance(point) >= MIN_CLUSTER_DISTANCE)
+		})
+}
+
+/// Lets telegraphed clusters out once their time is up.
+fn hatch_clusters(
+	time: Res<Time>,
+	mut pending: ResMut<PendingClusters>,
+	mut telegraphs: Query<&mut SpawnTelegraph>,
+	mut commands: Commands,
+	mut spawn_enemy: EventWriter<SpawnEnemy>,
+) {
+	pending.0.retain_mut(|cluster| {
+		cluster.timer.tick(time.delta());
+		if !cluster.timer.finished() {
+			if let Ok(mut telegraph) = telegraphs.get_mut(cluster.telegraph) {
+				telegraph.progress = cluster.timer.fraction();
 			}
+			return true;
 		}
+		commands.entity(cluster.telegraph).despawn();
+		spawn_enemy.write_batch(cluster.slimes.drain(..));
+		false
 	});
 }
 
+/// A cluster of slimes around `center`, as big and as mixed as `wave` calls
+/// for.
+pub fn random_cluster(
+	rng: &mut impl Rng,
+	palette: &SlimePalette,
+	wave: &WaveSettings,
+	center: Vec2,
+) -> Vec<SpawnEnemy> {
+	let mut enemy_types = vec![Enemy::random(palette, rng)];
+	while rng.gen_range(0.0..1.0) < wave.color_mix {
+		enemy_types.push(Enemy::random(palette, rng));
+	}
+	// Mix in mismatched charges so clusters rarely cancel out pairwise.
+	if rng.gen_range(0.0..1.0) < 0.5 {
+		enemy_types.push(enemy_types[0].counterweight());
+	}
+	let count = (wave.cluster_size * rng.gen_range(0.5..1.5))
+		.round()
+		.max(1.0) as usize;
+	(0..count)
+		.map(|_| {
+			let position = center
+				+ Vec2::new(
+					rng.gen_range(-CLUSTER_RADIUS..CLUSTER_RADIUS),
+					rng.gen_range(-CLUSTER_RADIUS..CLUSTER_RADIUS),
+				);
+			let mut enemy = enemy_types[rng.gen_range(0..enemy_types.len())];
+			let archetype = Archetype::random(rng);
+			// Splitters split into two slimes of magnitude 1.
+			if archetype == Archetype::Splitter {
+				enemy = enemy.with_magnitude(2);
+			}
+			SpawnEnemy {
+				position,
+				enemy,
+				archetype,
+			}
+		})
+		.collect()
+}
+
 #[derive(Event, Debug)]
 pub struct SpawnEnemy {
+	pub position: Vec2,
+	pub enemy: Enemy,
+	pub archetype: Archetype,
+}
+
+pub fn handle_spawn_enemy(
+	mut commands: Commands,
+	mut spawn_enemy: EventReader<SpawnEnemy>,
+	current_wave: Res<CurrentWave>,
+	mut rng: ResMut<GameRng>,
+) {
+	for spawn_enemy in spawn_enemy.read() {
+		let mut speed = MaxInternalVelocity::random(rng.gameplay());
+		speed.0 *= current_wave.0.speed;
+		let slime = spawn_slime(
+			&mut commands,
+			spawn_enemy.position,
+			spawn_enemy.enemy,
+			speed,
+		);
+		commands.entity(slime).insert(spawn_enemy.archetype);
+	}
+}
+
+/// Spawns a slime that walks toward the player and can be clicked into a chain.
+pub fn spawn_slime(
+	commands: &mut Commands,
 	position: Vec2,
 	enemy: Enemy,
+	speed: MaxInternalVelocity,
+) -> Entity {
+	let translation = position.extend(0.0);
+	commands
+		.spawn((
+			Transform::from_translation(translation),
+			Interpolated::at(translation),
+			enemy,
+			speed,
+			Velocity(Vec3::new(0.0, 0.0, 0.0)),
+			Pickable::default(),
+			StateScoped(GameState::Game),
+		))
+		.observe(on_click_enemy)
+		.observe(on_right_click_enemy)
+		.id()
 }
 
-fn handle_spawn_enemy(
+/// Gives spawned slimes their sprite, and re-tints it whenever [`Enemy`] is
+/// replaced. Slimes that already have a sprite, like the tutorial's, are only
+/// tinted.
+fn dress_enemy(
+	trigger: Trigger<OnInsert, Enemy>,
 	mut commands: Commands,
-	mut spawn_enemy: EventReader<SpawnEnemy>,
+	mut enemies: Query<(&Enemy, Option<&mut Sprite>)>,
+	palette: Res<SlimePalette>,
 	asset_server: Res<AssetServer>,
 ) {
-	for spawn_enemy in spawn_enemy.read() {
-		commands
-			.spawn((
-				Sprite {
-					image: asset_server.load("images/slime.png"),
-					rect: Some(Rect::new(0.0, 16.0, 16.0 * 2.0, 16.0 * 2.0)),
-					..default()
-				},
-				Transform::from_translation(Vec3::new(
-					spawn_enemy.position.x,
-					spawn_enemy.position.y,
-					0.0,
-				)),
-				spawn_enemy.enemy.clone(),
-				MaxInternalVelocity::random(),
-				Velocity(Vec3::new(0.0, 0.0, 0.0)),
-				Pickable::default(),
-				StateScoped(GameState::Game),
-			))
-			.observe(on_mouse_over_enemy)
-			.observe(on_mouse_no_longer_over_enemy)
-			.observe(on_click_enemy);
+	let Ok((enemy, sprite)) = enemies.get_mut(trigger.target()) else {
+		return;
+	};
+	if let Some(mut sprite) = sprite {
+		sprite.color = palette.color_of(enemy);
+		return;
+	}
+	commands
+		.entity(trigger.target())
+		.insert(Sprite {
+			image: asset_server.load("images/slime.png"),
+			rect: Some(Rect::new(0.0, 16.0, 16.0 * 2.0, 16.0 * 2.0)),
+			color: palette.color_of(enemy),
+			custom_size: Some(enemy.sprite_size()),
+			..default()
+		})
+		.observe(on_mouse_over_enemy)
+		.observe(on_mouse_no_longer_over_enemy);
+}
+
+/// A faint slime shape where a cluster is about to land, firming up as it gets
+/// closer.
+fn dress_telegraph(
+	trigger: Trigger<OnAdd, SpawnTelegraph>,
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+) {
+	commands.entity(trigger.target()).insert(Sprite {
+		image: asset_server.load("images/slime.png"),
+		rect: Some(Rect::new(0.0, 16.0, 16.0 * 2.0, 16.0 * 2.0)),
+		color: Color::WHITE.with_alpha(0.0),
+		custom_size: Some(Vec2::splat(CLUSTER_RADIUS * 2.0)),
+		..default()
+	});
+}
+
+fn pulse_telegraphs(
+	time: Res<Time>,
+	mut telegraphs: Query<(&SpawnTelegraph, &mut Sprite)>,
+) {
+	let pulse = (time.elapsed_secs() * 12.0).sin() * 0.5 + 0.5;
+	for (telegraph, mut sprite) in telegraphs.iter_mut() {
+		let alpha = telegraph.progress * (0.3 + 0.4 * pulse);
+		sprite.color = Color::WHITE.with_alpha(alpha);
+	}
+}
+
+/// The camera's visible size during the current run, carried over to the next.
+#[derive(Resource, Default)]
+struct MeasuredView(Option<UVec2>);
+
+fn measure_view(
+	camera: Single<(&Camera, &GlobalTransform), With<Camera2d>>,
+	mut measured: ResMut<MeasuredView>,
+) {
+	let (camera, transform) = *camera;
+	let Some(size) = camera.logical_viewport_size() else {
+		return;
+	};
+	let corners = (
+		camera.viewport_to_world_2d(transform, Vec2::ZERO),
+		camera.viewport_to_world_2d(transform, size),
+	);
+	if let (Ok(top_left), Ok(bottom_right)) = corners {
+		let view = (bottom_right - top_left).abs().ceil().as_uvec2();
+		measured.0 = Some(view);
+	}
+}
+
+fn carry_view_to_next_run(
+	measured: Res<MeasuredView>,
+	mut spawn_view: ResMut<SpawnView>,
+) {
+	if let Some(view) = measured.0 {
+		spawn_view.0 = view;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rand::SeedableRng;
+	use rand_chacha::ChaCha8Rng;
+
+	#[test]
+	fn spawn_points_stay_out_of_view_and_inside_the_map() {
+		let mut rng = ChaCha8Rng::seed_from_u64(5);
+		let view = UVec2::new(1280, 720);
+		// Pressed into a corner, so most of the band around the player is off
+		// the map.
+		let player = MAP_RADI - 1920.0;
+		let taken = [player + Vec2::new(900.0, 0.0)];
+		for _ in 0..200 {
+			let point = pick_spawn_point(&mut rng, player, view, &taken).unwrap();
+			let offset = (point - player).abs();
+			assert!(offset.x > 640.0 || offset.y > 360.0, "{point} is in view");
+			assert!(point.distance(player) >= MIN_PLAYER_DISTANCE);
+			assert!(point.x.abs() < MAP_RADI.x && point.y.abs() < MAP_RADI.y);
+			assert!(point.distance(taken[0]) >= MIN_CLUSTER_DISTANCE);
+		}
+	}
+
+	#[test]
+	fn crowded_clusters_are_skipped() {
+		let mut rng = ChaCha8Rng::seed_from_u64(5);
+		let taken: Vec<Vec2> = (-40..40)
+			.flat_map(|x| (-40..40).map(move |y| Vec2::new(x as f32, y as f32) * 100.0))
+			.collect();
+		let point =
+			pick_spawn_point(&mut rng, Vec2::ZERO, UVec2::new(1280, 720), &taken);
+		assert_eq!(point, None);
 	}
 }
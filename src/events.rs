@@ -0,0 +1,108 @@
+//! Gameplay events. Gameplay systems only report what happened; audio, screen
+//! shake, scoring and anything else react to these without reaching into the
+//! systems that send them.
+
+use crate::Enemy;
+use bevy::prelude::*;
+
+pub struct GameEventsPlugin;
+impl Plugin for GameEventsPlugin {
+	fn build(&self, app: &mut App) {
+		app.add_event::<SlimeChained>()
+			.add_event::<SlimeUnchained>()
+			.add_event::<ChainDetonationStarted>()
+			.add_event::<SlimeSquished>()
+			.add_event::<SlimePopped>()
+			.add_event::<ChainDetonationFinished>()
+			.add_event::<PlayerHit>()
+			.add_event::<BossArrived>()
+			.add_event::<BossHit>()
+			.add_event::<BossSlammed>()
+			.add_event::<BossDefeated>()
+			.add_event::<ActionRejected>();
+	}
+}
+
+/// A slime was attached to the chain at index `chain`.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct SlimeChained {
+	pub entity: Entity,
+	pub chain: usize,
+}
+
+/// A slime left its chain without popping, either detached by the player or
+/// despawned mid-chain.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct SlimeUnchained {
+	pub entity: Entity,
+}
+
+/// The chain at index `chain` started detonating `links` slimes.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ChainDetonationStarted {
+	pub chain: usize,
+	pub links: usize,
+}
+
+/// A slime in a chain reaction started squishing and will pop shortly.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct SlimeSquished {
+	pub entity: Entity,
+	pub enemy: Enemy,
+	pub position: Vec3,
+}
+
+/// A slime popped during a chain reaction. `combo_index` is the combo shown
+/// above it.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct SlimePopped {
+	pub entity: Entity,
+	pub enemy: Enemy,
+	pub position: Vec3,
+	pub combo_index: u32,
+}
+
+/// A chain reaction ended, earning `score` points.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ChainDetonationFinished {
+	pub score: u32,
+}
+
+/// A slime touched the player.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct PlayerHit {
+	pub slime: Entity,
+}
+
+/// A boss slime appeared.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct BossArrived {
+	pub entity: Entity,
+}
+
+/// A boss popped as a link of a chain reaction. It loses a point of health
+/// rather than popping for good.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct BossHit {
+	pub entity: Entity,
+}
+
+/// A boss slammed the ground, knocking out a player within `radius`.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct BossSlammed {
+	pub entity: Entity,
+	pub position: Vec3,
+	pub radius: f32,
+}
+
+/// A boss ran out of health and popped.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct BossDefeated {
+	pub entity: Entity,
+	pub position: Vec3,
+}
+
+/// The player tried something that isn't possible right now, like detonating
+/// an unbalanced chain or detaching from an empty one.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ActionRejected;
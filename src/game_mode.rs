@@ -0,0 +1,198 @@
+//! How a run is played and how it ends. The mode is picked on the mode select
+//! screen and read by spawning, [`handle_hit`](crate::player) and the end of
+//! the run.
+
+use crate::Simulation;
+use crate::menus::GameState;
+use bevy::prelude::*;
+use std::time::Duration;
+
+pub struct GameModePlugin;
+impl Plugin for GameModePlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<GameMode>();
+		app.init_resource::<RunClock>();
+		app.add_systems(OnEnter(GameState::Game), reset_run_clock);
+		app.add_systems(FixedUpdate, tick_run_clock.in_set(Simulation::Input));
+		app.add_systems(FixedUpdate, end_run_on_time_up.in_set(Simulation::Outcome));
+	}
+}
+
+/// How long a Time Attack run lasts.
+pub const TIME_ATTACK_LENGTH: Duration = Duration::from_secs(3 * 60);
+/// How long each Survival wave spawns slimes for.
+pub const WAVE_LENGTH: Duration = Duration::from_secs(30);
+/// The quiet spell after each wave.
+pub const WAVE_BREAK: Duration = Duration::from_secs(10);
+
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum GameMode {
+	/// Until a slime touches the player.
+	#[default]
+	Endless,
+	/// As many points as possible in [`TIME_ATTACK_LENGTH`].
+	TimeAttack,
+	/// Slimes can't hurt the player and nothing is ranked.
+	Zen,
+	/// Waves that spawn faster and faster, with a break after each.
+	SurvivalWaves,
+}
+
+impl GameMode {
+	pub const ALL: [GameMode; 4] = [
+		GameMode::Endless,
+		GameMode::TimeAttack,
+		GameMode::Zen,
+		GameMode::SurvivalWaves,
+	];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			GameMode::Endless => "Endless",
+			GameMode::TimeAttack => "Time Attack",
+			GameMode::Zen => "Zen",
+			GameMode::SurvivalWaves => "Survival Waves",
+		}
+	}
+
+	pub fn description(self) -> &'static str {
+		match self {
+			GameMode::Endless => "Play until a slime touches you.",
+			GameMode::TimeAttack => "Score as much as you can in three minutes.",
+			GameMode::Zen => "Slimes can't hurt you. Nothing is ranked.",
+			GameMode::SurvivalWaves => {
+				"Survive waves of slimes, with a rest after each."
+			}
+		}
+	}
+
+	/// What scores from this mode are tagged with on the leaderboard.
+	pub fn tag(self) -> &'static str {
+		match self {
+			GameMode::Endless => "endless",
+			GameMode::TimeAttack => "time-attack",
+			GameMode::Zen => "zen",
+			GameMode::SurvivalWaves => "waves",
+		}
+	}
+
+	pub fn is_ranked(self) -> bool {
+		self != GameMode::Zen
+	}
+
+	pub fn player_can_die(self) -> bool {
+		self != GameMode::Zen
+	}
+
+	pub fn time_limit(self) -> Option<Duration> {
+		match self {
+			GameMode::TimeAttack => Some(TIME_ATTACK_LENGTH),
+			_ => None,
+		}
+	}
+
+	/// How much faster than usual clusters spawn `elapsed` into a run, or
+	/// `None` while nothing should spawn.
+	pub fn spawn_rate(self, elapsed: Duration) -> Option<f32> {
+		match self {
+			GameMode::SurvivalWaves => match Wave::at(elapsed) {
+				Wave::Spawning { number, .. } => {
+					Some((1.0 + 0.15 * (number - 1) as f32).min(3.0))
+				}
+				Wave::Break { .. } => None,
+			},
+			_ => Some(1.0),
+		}
+	}
+}
+
+/// Where a Survival Waves run is. Waves are numbered from 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wave {
+	Spawning {
+		number: u32,
+		remaining: Duration,
+	},
+	/// The break after wave `number`.
+	Break {
+		number: u32,
+		remaining: Duration,
+	},
+}
+
+impl Wave {
+	pub fn at(elapsed: Duration) -> Self {
+		let cycle = WAVE_LENGTH + WAVE_BREAK;
+		let number = (elapsed.as_nanos() / cycle.as_nanos()) as u32 + 1;
+		let into_cycle = elapsed - cycle * (number - 1);
+		if into_cycle < WAVE_LENGTH {
+			Wave::Spawning {
+				number,
+				remaining: WAVE_LENGTH - into_cycle,
+			}
+		} else {
+			Wave::Break {
+				number,
+				remaining: cycle - into_cycle,
+			}
+		}
+	}
+}
+
+/// Simulation time since the run started, not counting pauses.
+#[derive(Resource, Default)]
+pub struct RunClock(pub Duration);
+
+fn reset_run_clock(mut run_clock: ResMut<RunClock>) {
+	run_clock.0 = Duration::ZERO;
+}
+
+fn tick_run_clock(time: Res<Time>, mut run_clock: ResMut<RunClock>) {
+	run_clock.0 += time.delta();
+}
+
+fn end_run_on_time_up(
+	mode: Res<GameMode>,
+	run_clock: Res<RunClock>,
+	mut game_state: ResMut<NextState<GameState>>,
+) {
+	if mode.time_limit().is_some_and(|limit| run_clock.0 >= limit) {
+		game_state.set(GameState::Shop);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn waves_alternate_with_breaks() {
+		let secs = Duration::from_secs;
+		assert_eq!(
+			Wave::at(secs(0)),
+			Wave::Spawning {
+				number: 1,
+				remaining: WAVE_LENGTH
+			}
+		);
+		assert_eq!(
+			Wave::at(secs(35)),
+			Wave::Break {
+				number: 1,
+				remaining: secs(5)
+			}
+		);
+		assert_eq!(
+			Wave::at(secs(41)),
+			Wave::Spawning {
+				number: 2,
+				remaining: secs(29)
+			}
+		);
+
+		let waves = GameMode::SurvivalWaves;
+		assert_eq!(waves.spawn_rate(secs(35)), None);
+		assert!(waves.spawn_rate(secs(41)) > waves.spawn_rate(secs(1)));
+		assert_eq!(GameMode::Zen.spawn_rate(secs(35)), Some(1.0));
+	}
+}
@@ -0,0 +1,102 @@
+//! All randomness in a run comes from [`GameRng`], reseeded when the run
+//! starts, so the same seed plays out the same map and spawns.
+
+use crate::menus::GameState;
+use bevy::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+pub struct GameRngPlugin;
+impl Plugin for GameRngPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<GameRng>();
+		app.init_resource::<RunSeed>();
+		app.add_systems(OnEnter(GameState::Game), seed_run);
+	}
+}
+
+/// The seed for the next run only. A fresh one is drawn when this is `None`.
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct RunSeed(pub Option<u64>);
+
+/// Two independent streams from one seed. Anything that affects the outcome of
+/// a run draws from `gameplay`; looks and sounds draw from `cosmetic`, so
+/// changing them never shifts what happens in the game.
+#[derive(Resource, Clone, Debug)]
+pub struct GameRng {
+	seed: u64,
+	gameplay: ChaCha8Rng,
+	cosmetic: ChaCha8Rng,
+}
+
+impl Default for GameRng {
+	fn default() -> Self {
+		Self::new(rand::random())
+	}
+}
+
+impl GameRng {
+	pub fn new(seed: u64) -> Self {
+		let gameplay = ChaCha8Rng::seed_from_u64(seed);
+		let mut cosmetic = ChaCha8Rng::seed_from_u64(seed);
+		cosmetic.set_stream(1);
+		Self {
+			seed,
+			gameplay,
+			cosmetic,
+		}
+	}
+
+	pub fn seed(&self) -> u64 {
+		self.seed
+	}
+
+	pub fn gameplay(&mut self) -> &mut ChaCha8Rng {
+		&mut self.gameplay
+	}
+
+	pub fn cosmetic(&mut self) -> &mut ChaCha8Rng {
+		&mut self.cosmetic
+	}
+}
+
+pub fn seed_run(mut run_seed: ResMut<RunSeed>, mut rng: ResMut<GameRng>) {
+	*rng = GameRng::new(run_seed.0.take().unwrap_or_else(rand::random));
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::enemy::random_cluster;
+	use crate::slime_palette::SlimePalette;
+	use crate::waves::WaveSettings;
+	use rand::RngCore;
+
+	fn draws(rng: &mut ChaCha8Rng) -> Vec<u32> {
+		(0..8).map(|_| rng.next_u32()).collect()
+	}
+
+	#[test]
+	fn same_seed_same_draws() {
+		let (mut a, mut b) = (GameRng::new(7), GameRng::new(7));
+		assert_eq!(draws(a.gameplay()), draws(b.gameplay()));
+		assert_eq!(draws(a.cosmetic()), draws(b.cosmetic()));
+		assert_ne!(draws(a.gameplay()), draws(a.cosmetic()));
+		assert_ne!(draws(GameRng::new(8).gameplay()), draws(b.gameplay()));
+	}
+
+	#[test]
+	fn cosmetic_draws_leave_gameplay_alone() {
+		let palette = SlimePalette::default();
+		let wave = WaveSettings::default();
+		let (mut a, mut b) = (GameRng::new(3), GameRng::new(3));
+		a.cosmetic().next_u32();
+		let cluster = |rng: &mut GameRng| {
+			random_cluster(rng.gameplay(), &palette, &wave, Vec2::ZERO)
+				.into_iter()
+				.map(|spawn| (spawn.position, spawn.enemy.charge()))
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(cluster(&mut a), cluster(&mut b));
+	}
+}
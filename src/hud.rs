@@ -0,0 +1,298 @@
+//! The in-game HUD. It is spawned once per run and each text is only rewritten
+//! when the value behind it changes.
+
+use crate::boss::Boss;
+use crate::chain::ChainGraph;
+use crate::game_mode::{GameMode, RunClock, Wave};
+use crate::menus::{GameState, PauseMenu};
+use crate::replay::Playback;
+use crate::slime_palette::SlimePalette;
+use crate::waves::CurrentWave;
+use crate::{ChainBalance, EnemyColor, Score};
+use bevy::color::palettes::css;
+use bevy::prelude::*;
+use bevy::time::Stopwatch;
+use std::time::Duration;
+
+pub struct HudPlugin;
+impl Plugin for HudPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<RunTimer>();
+		app.add_systems(OnEnter(GameState::Game), (reset_run_timer, spawn_hud));
+		app.add_systems(
+			Update,
+			tick_run_timer
+				.run_if(in_state(GameState::Game))
+				.run_if(in_state(PauseMenu::Unpaused)),
+		);
+		app.add_systems(
+			Update,
+			(
+				update_score_text.run_if(resource_changed::<Score>),
+				update_chain_text.run_if(resource_exists_and_changed::<ChainGraph>),
+				update_balance_meter.run_if(resource_changed::<ChainBalance>),
+				update_timer_text,
+				update_mode_text,
+				update_intensity_text.run_if(resource_changed::<CurrentWave>),
+				update_boss_bar,
+			)
+				.run_if(in_state(GameState::Game)),
+		);
+	}
+}
+
+/// How long the current run has been going, not counting pauses.
+#[derive(Resource, Default)]
+pub struct RunTimer(pub Stopwatch);
+
+#[derive(Component)]
+struct ScoreText;
+
+#[derive(Component)]
+struct ChainText;
+
+#[derive(Component)]
+struct TimerText;
+
+#[derive(Component)]
+struct ModeText;
+
+#[derive(Component)]
+struct IntensityText;
+
+/// The boss health bar, shown while a boss is out.
+#[derive(Component)]
+struct BossBar;
+
+#[derive(Component)]
+struct BossBarFill;
+
+/// One colour's entry in the balance meter, by index in the [`SlimePalette`].
+#[derive(Component)]
+struct BalanceMeterEntry(usize);
+
+fn reset_run_timer(mut run_timer: ResMut<RunTimer>) {
+	run_timer.0.reset();
+}
+
+fn tick_run_timer(time: Res<Time>, mut run_timer: ResMut<RunTimer>) {
+	run_timer.0.tick(time.delta());
+}
+
+fn spawn_hud(
+	mut commands: Commands,
+	score: Res<Score>,
+	chain_graph: Option<Res<ChainGraph>>,
+	balance: Res<ChainBalance>,
+	palette: Res<SlimePalette>,
+	playback: Res<State<Playback>>,
+	mode: Res<GameMode>,
+	current_wave: Res<CurrentWave>,
+) {
+	let hud = commands
+		.spawn((
+			Name::new("HUD"),
+			Node {
+				position_type: PositionType::Absolute,
+				width: Val::Percent(100.0),
+				height: Val::Percent(15.0),
+				align_items: AlignItems::Center,
+				justify_content: JustifyContent::Center,
+				flex_direction: FlexDirection::Column,
+				row_gap: Val::Px(20.0),
+				..default()
+			},
+			Pickable::IGNORE,
+			StateScoped(GameState::Game),
+			children![
+				(Text(score_text(&score)), ScoreText),
+				(
+					Text(
+						chain_graph
+							.as_deref()
+							.map(chain_text)
+							.unwrap_or_default()
+					),
+					ChainText
+				),
+				(Text(timer_text(0)), TimerText),
+				(Text(mode_text(*mode, Duration::ZERO)), ModeText),
+				(Text(intensity_text(&current_wave)), IntensityText),
+			],
+		))
+		.id();
+	if *playback.get() == Playback::Replay {
+		commands.spawn((Text::new("Watching a replay"), ChildOf(hud)));
+	}
+	let meter = commands
+		.spawn((
+			Name::new("Balance Meter"),
+			Node {
+				flex_direction: FlexDirection::Row,
+				column_gap: Val::Px(20.0),
+				..default()
+			},
+			ChildOf(hud),
+		))
+		.id();
+	for color in palette.colors() {
+		let sum = balance.0.get(color.0).copied().unwrap_or_default();
+		commands.spawn((
+			Text(balance_text(&palette, color.0, sum)),
+			TextColor(palette.get(color).base),
+			BalanceMeterEntry(color.0),
+			ChildOf(meter),
+		));
+	}
+	commands.spawn((
+		Name::new("Boss Bar"),
+		Node {
+			flex_direction: FlexDirection::Column,
+			align_items: AlignItems::Center,
+			row_gap: Val::Px(4.0),
+			..default()
+		},
+		Visibility::Hidden,
+		BossBar,
+		ChildOf(hud),
+		children![
+			Text::new("Boss"),
+			(
+				Node {
+					width: Val::Px(300.0),
+					height: Val::Px(12.0),
+					..default()
+				},
+				BackgroundColor(Color::BLACK.with_alpha(0.6)),
+				children![(
+					Node {
+						width: Val::Percent(100.0),
+						height: Val::Percent(100.0),
+						..default()
+					},
+					BackgroundColor(Color::from(css::CRIMSON)),
+					BossBarFill,
+				)],
+			),
+		],
+	));
+}
+
+fn score_text(score: &Score) -> String {
+	format!("Score: {}", score.0)
+}
+
+fn chain_text(chain_graph: &ChainGraph) -> String {
+	format!(
+		"Chain {}/{}: {} links (Tab to switch)",
+		chain_graph.active() + 1,
+		chain_graph.len(),
+		chain_graph.chain().len()
+	)
+}
+
+fn timer_text(seconds: u64) -> String {
+	format!("Time: {}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// The mode, and for timed modes how long is left.
+fn mode_text(mode: GameMode, elapsed: Duration) -> String {
+	let left = |remaining: Duration| {
+		let seconds = remaining.as_secs_f32().ceil() as u64;
+		format!("{}:{:02}", seconds / 60, seconds % 60)
+	};
+	match mode {
+		GameMode::TimeAttack => {
+			let limit = mode.time_limit().unwrap_or_default();
+			format!("Time Attack: {} left", left(limit.saturating_sub(elapsed)))
+		}
+		GameMode::SurvivalWaves => match Wave::at(elapsed) {
+			Wave::Spawning { number, remaining } => {
+				format!("Wave {number}: {} left", left(remaining))
+			}
+			Wave::Break { number, remaining } => {
+				format!("Wave {} in {}", number + 1, left(remaining))
+			}
+		},
+		_ => mode.name().to_string(),
+	}
+}
+
+fn intensity_text(current_wave: &CurrentWave) -> String {
+	format!("Intensity: {:.0}%", current_wave.0.intensity * 100.0)
+}
+
+fn balance_text(palette: &SlimePalette, index: usize, sum: i32) -> String {
+	let name = &palette.get(EnemyColor(index)).name;
+	format!("{name} {sum:+}")
+}
+
+fn update_score_text(score: Res<Score>, mut text: Single<&mut Text, With<ScoreText>>) {
+	text.0 = score_text(&score);
+}
+
+fn update_chain_text(
+	chain_graph: Res<ChainGraph>,
+	mut text: Single<&mut Text, With<ChainText>>,
+) {
+	text.0 = chain_text(&chain_graph);
+}
+
+fn update_balance_meter(
+	balance: Res<ChainBalance>,
+	palette: Res<SlimePalette>,
+	mut entries: Query<(&BalanceMeterEntry, &mut Text)>,
+) {
+	for (BalanceMeterEntry(index), mut text) in entries.iter_mut() {
+		let sum = balance.0.get(*index).copied().unwrap_or_default();
+		text.0 = balance_text(&palette, *index, sum);
+	}
+}
+
+fn update_timer_text(
+	run_timer: Res<RunTimer>,
+	mut text: Single<&mut Text, With<TimerText>>,
+) {
+	// Only touch the text when the displayed second rolls over.
+	let new_text = timer_text(run_timer.0.elapsed().as_secs());
+	if text.0 != new_text {
+		text.0 = new_text;
+	}
+}
+
+fn update_intensity_text(
+	current_wave: Res<CurrentWave>,
+	mut text: Single<&mut Text, With<IntensityText>>,
+) {
+	let new_text = intensity_text(&current_wave);
+	if text.0 != new_text {
+		text.0 = new_text;
+	}
+}
+
+fn update_mode_text(
+	mode: Res<GameMode>,
+	run_clock: Res<RunClock>,
+	mut text: Single<&mut Text, With<ModeText>>,
+) {
+	let new_text = mode_text(*mode, run_clock.0);
+	if text.0 != new_text {
+		text.0 = new_text;
+	}
+}
+
+fn update_boss_bar(
+	bosses: Query<&Boss>,
+	mut bar: Single<&mut Visibility, With<BossBar>>,
+	mut fill: Single<&mut Node, With<BossBarFill>>,
+) {
+	let Some(boss) = bosses.iter().next() else {
+		bar.set_if_neq(Visibility::Hidden);
+		return;
+	};
+	bar.set_if_neq(Visibility::Inherited);
+	let width = Val::Percent(100.0 * boss.health as f32 / boss.max_health as f32);
+	if fill.width != width {
+		fill.width = width;
+	}
+}
@@ -0,0 +1,75 @@
+//! Render interpolation for entities simulated in `FixedUpdate`.
+//!
+//! During the fixed loop `Transform` holds the simulated translation. Around it
+//! the last two simulated translations are recorded, and once the loop is done
+//! `Transform` is blended between them by how far the frame has run into the
+//! next tick, so motion stays smooth at any refresh rate.
+
+use bevy::app::RunFixedMainLoopSystem;
+use bevy::prelude::*;
+
+/// Simulation ticks per second. Movement tuning constants are per tick at
+/// this rate.
+pub const SIMULATION_HZ: f64 = 60.0;
+
+pub struct InterpolationPlugin;
+impl Plugin for InterpolationPlugin {
+	fn build(&self, app: &mut App) {
+		app.insert_resource(Time::<Fixed>::from_hz(SIMULATION_HZ));
+		app.add_systems(FixedFirst, restore_simulated_translation);
+		app.add_systems(FixedLast, record_simulated_translation);
+		app.add_systems(
+			RunFixedMainLoop,
+			interpolate_translation
+				.in_set(RunFixedMainLoopSystem::AfterFixedMainLoop),
+		);
+	}
+}
+
+/// How many ticks at [`SIMULATION_HZ`] the current fixed step covers. Per-tick
+/// constants are scaled by this so they don't depend on the tick rate.
+pub fn ticks(time: &Time) -> f32 {
+	time.delta_secs() * SIMULATION_HZ as f32
+}
+
+/// The last two simulated translations of an entity moved in `FixedUpdate`.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Interpolated {
+	previous: Vec3,
+	current: Vec3,
+}
+
+impl Interpolated {
+	pub fn at(translation: Vec3) -> Self {
+		Self {
+			previous: translation,
+			current: translation,
+		}
+	}
+}
+
+fn restore_simulated_translation(
+	mut query: Query<(&mut Transform, &mut Interpolated)>,
+) {
+	for (mut transform, mut interpolated) in query.iter_mut() {
+		interpolated.previous = interpolated.current;
+		transform.translation = interpolated.current;
+	}
+}
+
+fn record_simulated_translation(mut query: Query<(&Transform, &mut Interpolated)>) {
+	for (transform, mut interpolated) in query.iter_mut() {
+		interpolated.current = transform.translation;
+	}
+}
+
+pub fn interpolate_translation(
+	fixed_time: Res<Time<Fixed>>,
+	mut query: Query<(&mut Transform, &Interpolated)>,
+) {
+	let overstep = fixed_time.overstep_fraction();
+	for (mut transform, interpolated) in query.iter_mut() {
+		transform.translation =
+			interpolated.previous.lerp(interpolated.current, overstep);
+	}
+}
@@ -1,58 +1,115 @@
+mod ai;
+mod archetype;
+mod boss;
+mod cascade;
+mod chain;
+mod chain_links;
+mod daily;
+mod detonation;
 mod enemy;
+mod events;
 mod explosion;
+mod game_mode;
+mod game_rng;
+mod hud;
+mod interpolation;
 mod menus;
 mod music;
 mod player;
+mod replay;
 mod screen_shake;
+mod sfx;
+mod shop;
+mod slime_palette;
+mod spatial;
+#[cfg(test)]
+mod test_harness;
 mod text_combo;
 mod theme;
+mod tick_input;
 mod tutorial_section;
+mod waves;
 
 use std::cmp::max;
 use std::collections::HashSet;
+use std::env;
 use std::f32::consts::PI;
 use std::hint::unreachable_unchecked;
 use std::ops::{Add, AddAssign, DerefMut, Div, Sub};
 use std::time::Duration;
 
-use crate::enemy::EnemyPlugin;
+use crate::ai::{AiPlugin, AiState, AiTuning};
+use crate::archetype::{
+	Archetype, ArchetypeClock, ArchetypePlugin, ArchetypeVisualsPlugin,
+};
+use crate::cascade::CascadeRules;
+use crate::boss::{Boss, BossPlugin, BossVisualsPlugin};
+use crate::chain::{
+	ChainGraph, Chained, PartialDetonation, longest_balanced_prefix, on_remove_chained,
+};
+use crate::chain_links::draw_chains;
+use crate::daily::DailyPlugin;
+use crate::detonation::{DetonationPlugin, DetonationVisualsPlugin};
+use crate::enemy::{EnemyPlugin, EnemyVisualsPlugin};
+use crate::events::{
+	ActionRejected, ChainDetonationFinished, GameEventsPlugin, SlimeChained,
+	SlimeUnchained,
+};
 use crate::explosion::FireParticleMaterial;
-use crate::menus::shop_menu::{ChainRadiusLevel, SlimeSlownessLevel};
+use crate::game_mode::GameModePlugin;
+use crate::game_rng::{GameRng, GameRngPlugin, seed_run};
+use crate::hud::HudPlugin;
+use crate::interpolation::{
+	InterpolationPlugin, SIMULATION_HZ, interpolate_translation, ticks,
+};
+use crate::shop::{ChainRadiusLevel, ShopPlugin, SlimeSlownessLevel};
 use crate::menus::{GameState, PauseMenu};
 use crate::music::MusicPlugin;
-use crate::player::{AnimationState, Direction, PlayerPlugin, PlayerState};
-use crate::screen_shake::{ScreenShakePlugin, SlimeDestroyed};
+use crate::player::{
+	AnimationState, Direction, PlayerPlugin, PlayerState, PlayerVisualsPlugin,
+};
+use crate::replay::{Playback, Recording, ReplayDirectory, ReplayPlugin, begin_replay};
+use crate::screen_shake::ScreenShakePlugin;
+use crate::sfx::SfxPlugin;
+use crate::slime_palette::SlimePalette;
+use crate::spatial::SpatialIndex;
 use crate::text_combo::{TextCombo, TextComboPlugin};
+use crate::tick_input::{PendingInput, SlimeClick, SlimeId, TickInput, TickInputPlugin};
+use crate::waves::{CurrentWave, WaveAssetPlugin, WaveDirectorPlugin};
 use bevy::asset::{AssetMetaCheck, AssetPlugin, Handle};
 use bevy::audio::{PlaybackSettings, Volume};
 use bevy::color::Color;
 use bevy::color::palettes::css;
 use bevy::ecs::children;
-use bevy::ecs::component::HookContext;
 use bevy::ecs::relationship::OrderedRelationshipSourceCollection;
-use bevy::ecs::system::RunSystemOnce;
-use bevy::ecs::world::DeferredWorld;
 use bevy::image::Image;
-use bevy::math::{EulerRot, Quat, Rect, Vec2, Vec3};
+use bevy::input::InputPlugin;
+use bevy::log::{LogPlugin, error, info};
+use bevy::math::{EulerRot, Quat, Rect, Vec2, Vec3, Vec3Swizzles};
 use bevy::prelude::EaseFunction::BounceOut;
 use bevy::prelude::{
-	AlignItems, Alpha, AudioPlayer, ButtonInput, Camera, ChildOf, Circle, Click,
-	ColorMaterial, ContainsEntity, Entity, Event, EventReader, EventWriter,
-	FlexDirection, GlobalTransform, IVec2, IntoScheduleConfigs, JustifyContent,
-	JustifyText, KeyCode, Local, Luminance, Mesh, Mesh2d, MeshMaterial2d,
-	MeshPickingPlugin, Mut, Node, OnAdd, OnEnter, OnRemove, OrthographicProjection,
-	Out, Over, Pickable, Plugin, Pointer, PositionType, Pressed, Rectangle, Resource,
-	Saturation, Single, StateScoped, Text, Text2d, TextLayout, Transform, Trigger, Val,
+	AlignItems, Alpha, AudioPlayer, Camera, ChildOf, Circle, Click, ColorMaterial,
+	ContainsEntity, Entity, Event, EventReader, EventWriter, FlexDirection,
+	GlobalTransform, IVec2, IntoScheduleConfigs, JustifyContent, JustifyText, Luminance,
+	Mesh, Mesh2d, MeshMaterial2d, MeshPickingPlugin, Mut, Node, OnAdd, OnEnter, OnExit,
+	OnRemove, OrthographicProjection, Out, Over, Pickable, Plugin, Pointer,
+	PointerButton, PositionType, Pressed, Rectangle, Resource, Saturation, Single,
+	State, StateScoped, SystemSet, Text, Text2d, TextLayout, Transform, Trigger, Val,
 	Window, With, Without, World, default, in_state,
 };
 use bevy::prelude::{BackgroundColor, SpawnRelated};
 use bevy::render::camera::{CameraProjection, SubCameraView};
 use bevy::render::primitives::Frustum;
+use bevy::state::app::{AppExtStates, StatesPlugin};
 use bevy::sprite::SpriteImageMode;
+use bevy::transform::TransformPlugin;
 use bevy::window::PrimaryWindow;
 use bevy::{
-	DefaultPlugins,
-	app::{App, Startup, Update},
+	DefaultPlugins, MinimalPlugins,
+	app::{
+		App, AppExit, FixedPreUpdate, FixedUpdate, PluginGroupBuilder, RunFixedMainLoop,
+		RunFixedMainLoopSystem, Startup, Update,
+	},
 	asset::{AssetServer, Assets},
 	core_pipeline::core_2d::Camera2d,
 	ecs::{
@@ -64,7 +121,7 @@ use bevy::{
 	prelude::{Deref, DerefMut, PluginGroup},
 	render::texture::ImagePlugin,
 	sprite::Sprite,
-	time::{Time, Timer, TimerMode},
+	time::{Fixed, Time, TimeUpdateStrategy, Timer, TimerMode},
 };
 use bevy_defer::{AsyncAccess, AsyncCommandsExtension, AsyncWorld};
 use bevy_ecs_tilemap::map::TilemapId;
@@ -84,43 +141,152 @@ use bevy_enoki::{
 use rand::Rng;
 use rand::distributions::Standard;
 use rand::prelude::Distribution;
-use random_number::random;
 
 fn main() {
-	App::new()
-		.add_plugins((
-			DefaultPlugins.set(ImagePlugin::default_nearest())
-				.set(AssetPlugin {
-					meta_check: AssetMetaCheck::Never,
-					..default()
-				}),
-			MeshPickingPlugin,
-			bevy_defer::AsyncPlugin::default_settings(),
-			menus::MenuPlugins,
-		))
-		.add_plugins(EnokiPlugin)
-		.add_plugins(Particle2dMaterialPlugin::<FireParticleMaterial>::default())
-		.add_plugins((
-			PlayerPlugin,
-			MusicPlugin,
-			EnemyPlugin,
-			ScreenShakePlugin,
-			TextComboPlugin,
-			MainGamePlugin,
-		))
-		.run();
-}
-
-fn setup_tilemap(mut commands: Commands, asset_server: Res<AssetServer>) {
+	let replay = replay::path_from_args().map(|path| {
+		replay::load(&path).map_err(|error| {
+			format!("couldn't load the replay at {}: {error}", path.display())
+		})
+	});
+	if env::args().any(|arg| arg == "--headless") {
+		let mut app = headless_app();
+		app.add_plugins(LogPlugin::default());
+		match replay {
+			Some(Ok(recording)) => {
+				// Nothing to wait for, so replays run one tick per update as
+				// fast as they can.
+				app.insert_resource(TimeUpdateStrategy::ManualDuration(
+					Time::<Fixed>::from_hz(SIMULATION_HZ).timestep(),
+				));
+				begin_replay(app.world_mut(), recording);
+			}
+			Some(Err(message)) => {
+				error!("{message}");
+				std::process::exit(1);
+			}
+			None => {}
+		}
+		app.add_systems(OnExit(GameState::Game), exit_after_run);
+		app.run();
+		return;
+	}
+	let mut app = App::new();
+	app.add_plugins((
+		DefaultPlugins.set(ImagePlugin::default_nearest())
+			.set(AssetPlugin {
+				meta_check: AssetMetaCheck::Never,
+				..default()
+			}),
+		SimulationPlugins,
+		PresentationPlugins,
+	))
+	.init_resource::<ReplayDirectory>();
+	match replay {
+		Some(Ok(recording)) => begin_replay(app.world_mut(), recording),
+		// The game still starts, at the main menu, if the file won't load.
+		Some(Err(message)) => error!("{message}"),
+		None => {}
+	}
+	app.run();
+}
+
+/// The gameplay itself: movement, spawning, chaining, detonations and scoring.
+/// Needs nothing beyond `MinimalPlugins` and the state, transform and input
+/// plugins.
+pub struct SimulationPlugins;
+impl PluginGroup for SimulationPlugins {
+	fn build(self) -> PluginGroupBuilder {
+		PluginGroupBuilder::start::<Self>()
+			.add(bevy_defer::AsyncPlugin::default_settings())
+			.add(GameRngPlugin)
+			.add(GameEventsPlugin)
+			.add(TickInputPlugin)
+			.add(ReplayPlugin)
+			.add(DailyPlugin)
+			.add(GameModePlugin)
+			.add(WaveDirectorPlugin)
+			.add(InterpolationPlugin)
+			.add(MainGamePlugin)
+			.add(PlayerPlugin)
+			.add(EnemyPlugin)
+			.add(ArchetypePlugin)
+			.add(AiPlugin)
+			.add(BossPlugin)
+			.add(DetonationPlugin)
+			.add(ShopPlugin)
+	}
+}
+
+/// Everything that needs a window, renderer or audio: sprites, particles,
+/// sound, the HUD and the menus.
+pub struct PresentationPlugins;
+impl PluginGroup for PresentationPlugins {
+	fn build(self) -> PluginGroupBuilder {
+		PluginGroupBuilder::start::<Self>()
+			.add(MeshPickingPlugin)
+			.add(EnokiPlugin)
+			.add(Particle2dMaterialPlugin::<FireParticleMaterial>::default())
+			.add(menus::MenuPlugins)
+			.add(MainGameVisualsPlugin)
+			.add(PlayerVisualsPlugin)
+			.add(EnemyVisualsPlugin)
+			.add(ArchetypeVisualsPlugin)
+			.add(BossVisualsPlugin)
+			.add(DetonationVisualsPlugin)
+			.add(MusicPlugin)
+			.add(ScreenShakePlugin)
+			.add(TextComboPlugin)
+			.add(SfxPlugin)
+			.add(HudPlugin)
+			.add(WaveAssetPlugin)
+	}
+}
+
+/// An app that runs the [`SimulationPlugins`] without a window, starting
+/// straight in [`GameState::Game`].
+pub fn headless_app() -> App {
+	let mut app = App::new();
+	app.add_plugins((
+		MinimalPlugins,
+		StatesPlugin,
+		TransformPlugin,
+		InputPlugin,
+		SimulationPlugins,
+	));
+	app.insert_state(GameState::Game);
+	app
+}
+
+/// Ends a `--headless` run once the player is hit or the replay runs out,
+/// logging how it went.
+fn exit_after_run(
+	score: Res<Score>,
+	recording: Res<Recording>,
+	mut app_exit: EventWriter<AppExit>,
+) {
+	info!(
+		"run over after {:.1}s with a score of {}",
+		recording.ticks.len() as f64 / SIMULATION_HZ,
+		score.0
+	);
+	app_exit.write(AppExit::Success);
+}
+
+fn setup_tilemap(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	mut rng: ResMut<GameRng>,
+) {
+	let rng = rng.cosmetic();
 	let texture_size = UVec2::new(16, 16);
 	let size = IVec2::new(MAP_RADI.x as i32 / 16, MAP_RADI.y as i32 / 16);
 
 	let plain = Vec2::new(40.0, 40.0);
 	for x in -size.x..size.x {
 		for y in -size.y..size.y {
-			let positions = if random!(0.0..1.0) <= 0.04 {
-				if random!(0.0..1.0) <= 0.8 {
-					match random!(0..8) {
+			let positions = if rng.gen_range(0.0..1.0) <= 0.04 {
+				if rng.gen_range(0.0..1.0) <= 0.8 {
+					match rng.gen_range(0..8) {
 						0 => Vec2::new(16.0 * 11.0, 16.0 * 2.0),
 						1 => Vec2::new(16.0 * 11.0, 16.0 * 3.0),
 						2 => Vec2::new(16.0 * 11.0, 16.0 * 4.0),
@@ -129,7 +295,7 @@ fn setup_tilemap(mut commands: Commands, asset_server: Res<AssetServer>) {
 						_ => Vec2::new(16.0 * 10.0, 16.0 * 2.0),
 					}
 				} else {
-					match random!(0..4) {
+					match rng.gen_range(0..4) {
 						0 => Vec2::new(16.0 * 9.0, 16.0 * 2.0),
 						1 => Vec2::new(16.0 * 9.0, 16.0 * 1.0),
 						2 => Vec2::new(16.0 * 8.0, 16.0 * 2.0),
@@ -171,11 +337,37 @@ fn camera_sync(
 pub struct MainGamePlugin;
 impl Plugin for MainGamePlugin {
 	fn build(&self, app: &mut App) {
-		app.add_event::<StartChainReaction>()
-			.add_systems(OnEnter(GameState::Game), setup)
+		app.init_state::<GameState>()
+			.init_state::<PauseMenu>()
+			.add_event::<StartChainReaction>()
+			.add_observer(on_remove_chained)
 			.init_resource::<Score>()
+			.init_resource::<PartialDetonation>()
+			.init_resource::<ChainBalance>()
+			.init_resource::<SpatialIndex>()
 			.add_systems(
-				Update,
+				FixedPreUpdate,
+				rebuild_spatial_index.run_if(in_state(GameState::Game)),
+			)
+			.init_resource::<SlimePalette>()
+			.init_resource::<CascadeRules>()
+			.configure_sets(
+				FixedUpdate,
+				(
+					Simulation::Input,
+					Simulation::Spawn,
+					Simulation::Movement,
+					Simulation::Chain,
+					Simulation::Detonation,
+					Simulation::Outcome,
+				)
+					.chain()
+					.run_if(in_state(GameState::Game))
+					.run_if(in_state(PauseMenu::Unpaused)),
+			)
+			.add_systems(FixedUpdate, apply_clicks.in_set(Simulation::Input))
+			.add_systems(
+				FixedUpdate,
 				(
 					move_player,
 					move_enemy,
@@ -183,25 +375,62 @@ impl Plugin for MainGamePlugin {
 					chain_slow_down,
 					move_enemy_2,
 					randomly_change_max_internal_velocity,
-					camera_sync.after(move_player),
 				)
-					.run_if(in_state(GameState::Game))
-					.run_if(in_state(PauseMenu::Unpaused)),
+					.chain()
+					.in_set(Simulation::Movement),
 			)
 			.add_systems(
-				Update,
+				FixedUpdate,
 				(
-					despawn,
-					(draw_chains, enemy_chainable_graphic, draw_chain_balance)
-						.run_if(in_state(GameState::Game)),
+					enemy_chainable_graphic,
+					detach_last_link,
+					cycle_active_chain,
+					update_chain_balance,
+					detonate_on_space,
 				)
-					.chain(),
+					.chain()
+					.in_set(Simulation::Chain),
 			)
 			.add_systems(
-				Update,
-				start_chain_reaction.run_if(in_state(GameState::Game)),
+				FixedUpdate,
+				add_detonation_score.in_set(Simulation::Outcome),
 			)
-			.add_systems(OnEnter(GameState::Game), setup_tilemap);
+			.add_systems(OnEnter(GameState::Game), reset_score);
+	}
+}
+
+/// The steps of a simulation tick, in order. Systems within each step are
+/// chained too, so a tick plays out the same way every time it is given the
+/// same input.
+#[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Simulation {
+	/// Apply the clicks in [`TickInput`].
+	Input,
+	Spawn,
+	Movement,
+	Chain,
+	Detonation,
+	/// Scoring and losing.
+	Outcome,
+}
+
+/// The camera, map and chains.
+pub struct MainGameVisualsPlugin;
+impl Plugin for MainGameVisualsPlugin {
+	fn build(&self, app: &mut App) {
+		app.add_systems(
+			OnEnter(GameState::Game),
+			(setup, setup_tilemap.after(seed_run)),
+		);
+		// After interpolation so the camera follows the drawn player.
+		app.add_systems(
+			RunFixedMainLoop,
+			camera_sync
+				.in_set(RunFixedMainLoopSystem::AfterFixedMainLoop)
+				.after(interpolate_translation)
+				.run_if(in_state(GameState::Game)),
+		);
+		app.add_systems(Update, draw_chains.run_if(in_state(GameState::Game)));
 	}
 }
 
@@ -239,201 +468,41 @@ fn setup(
 	commands.insert_resource(ChainAsset(asset_server.load("images/pink_chain.png")));
 }
 
-fn start_chain_reaction(
-	event_reader: EventReader<StartChainReaction>,
-	query: Query<(Entity, &Chained)>,
-	player: Single<Entity, With<Player>>,
-	mut commands: Commands,
-	asset_server: Res<AssetServer>,
-	mut res: ResMut<LastEntityChained>,
-	mut materials: ResMut<Assets<FireParticleMaterial>>,
+fn add_detonation_score(
+	mut detonation_finished: EventReader<ChainDetonationFinished>,
+	mut score: ResMut<Score>,
 ) {
-	if event_reader.is_empty() {
-		return;
-	}
-	let mut last_chained_entity = None;
-	for (e, Chained { prev }) in query.iter() {
-		if *prev == *player {
-			last_chained_entity = Some(e);
-		}
-	}
-
-	let Some(mut last_chained_entity) = last_chained_entity else {
-		commands.spawn(AudioPlayer::new(asset_server.load("audio/error.ogg")));
-		return; // no chained entities at all lol
-	};
-	let mut entities_to_destroy = vec![];
-	loop {
-		entities_to_destroy.push(last_chained_entity);
-		let mut changed = false;
-		for (e, chained) in query.iter() {
-			if chained.prev == last_chained_entity {
-				last_chained_entity = e;
-				changed = true;
-			}
-		}
-		if !changed {
-			break;
-		}
-	}
-
-	// Bodge because my algorithm is bad somehow and is missing some entities
-	for (e, _) in query.iter() {
-		if !entities_to_destroy.contains(&e) {
-			entities_to_destroy.push_back(e);
-		}
+	for ChainDetonationFinished { score: points } in detonation_finished.read() {
+		score.0 += points;
 	}
-	let material_handle = materials.add(FireParticleMaterial {
-		texture: asset_server.load("images/noise.png"),
-	});
-
-	commands.spawn(());
-	//let awa = asset_server.load_untyped("shaders/ice.particle.ron");
-	res.0 = *player;
-	commands.spawn_task(move || async {
-		let mut combo = 1;
-		//let awa = awa;
-		for (i, entity) in entities_to_destroy.into_iter().enumerate() {
-			let sleep_duration =
-				Duration::from_secs_f32((0.5 / 1.2_f32.powf(i as f32)).max(0.05));
-			AsyncWorld.sleep(sleep_duration).await;
-			AsyncWorld.run(|world: &mut World| {
-				world
-					.run_system_once(
-						move |mut commands: Commands,
-						      asset_server: Res<AssetServer>| {
-							commands.spawn((
-								AudioPlayer::new(
-									asset_server.load("audio/slime-squish.ogg"),
-								),
-								PlaybackSettings::ONCE
-									.with_speed(
-										0.9 / (3.0 / 1.1_f32.powf(i as f32)).max(0.3),
-									)
-									.with_volume(Volume::Linear(5.5)),
-							));
-						},
-					)
-					.unwrap();
-			});
-			AsyncWorld.run(move |world: &mut World| {
-				world
-					.run_system_once(
-						move |mut commands: Commands,
-						      particles: Res<Assets<Particle2dEffect>>,
-						      asset_server: Res<AssetServer>,
-						      mut materials: ResMut<Assets<FireParticleMaterial>>,
-						      transforms: Query<&GlobalTransform>,
-						      enemies: Query<&Enemy>| {
-							let material_handle = materials.add(FireParticleMaterial {
-								texture: asset_server.load("images/noise.png"),
-							});
-							let Ok(enemy) = enemies.get(entity) else {
-								return;
-							};
-							let color = Color::from(*enemy);
-							commands.spawn((
-								ParticleEffectHandle(asset_server.add(
-									Particle2dEffect {
-										spawn_rate: 0.0,
-										spawn_amount: 50,
-										emission_shape: Default::default(), // Equivalent to Point
-										lifetime: Rval::new(0.3, 0.5),
-										linear_speed: Some(Rval::new(25.0, 25.0)),
-										linear_acceleration: Some(Rval::new(
-											-1.0, -1.5,
-										)),
-										direction: Some(Rval::new(
-											Vec2::new(0.1, 0.1),
-											0.314,
-										)),
-										angular_speed: Some(Rval::new(200.0, 300.0)),
-										angular_acceleration: Some(Rval::new(
-											-300.0, -200.0,
-										)),
-										gravity_direction: None,
-										gravity_speed: None,
-										scale: Some(Rval::new(0.0, 100.0)),
-										linear_damp: Some(Rval::new(0.8, 20.0)),
-										angular_damp: Some(Rval::new(0.0, 10.0)),
-										scale_curve: Some(MultiCurve {
-											points: vec![
-												(10.0, 0.0, None),
-												(30.0, 1.0, Some(BounceOut)),
-											],
-										}),
-										color: Some(color.into()),
-										color_curve: None,
-									},
-								)),
-								OneShot::Despawn,
-								ParticleSpawnerState::default(),
-								ParticleSpawner(material_handle),
-								transforms.get(entity).unwrap().compute_transform(),
-							));
-						},
-					)
-					.unwrap();
-			});
-			AsyncWorld.sleep_frames(5).await;
-			if !AsyncWorld.entity(entity).query::<&Transform>().exists() {
-				continue;
-			}
-			AsyncWorld.send_event(SlimeDestroyed).unwrap();
-			AsyncWorld.spawn_bundle((
-				AsyncWorld
-					.entity(entity)
-					.query::<&Transform>()
-					.get(|a| a.clone())
-					.unwrap(),
-				Text2d::new(combo.to_string()),
-				TextLayout::new_with_justify(JustifyText::Center),
-				TextCombo,
-			));
-			AsyncWorld.entity(entity).despawn();
-			combo += 2;
-		}
-		AsyncWorld.resource_scope(|mut score: Mut<Score>| {
-			score.0 += combo;
-		});
-		Ok(())
-	});
 }
 
-#[derive(Event)]
-pub struct AddToScore(u32);
+fn reset_score(mut score: ResMut<Score>) {
+	score.0 = 0;
+}
 
-fn chain_slow_down(mut query: Query<&mut Velocity, With<Chained>>) {
+fn chain_slow_down(
+	time: Res<Time>,
+	mut query: Query<&mut Velocity, With<Chained>>,
+) {
+	let factor = 1.06_f32.powf(ticks(&time));
 	for mut v in query.iter_mut() {
-		v.0 *= 1.06;
+		v.0 *= factor;
 	}
 }
 
-#[derive(Clone, Copy, Debug)]
-pub enum EnemyColor {
-	Red,
-	Green,
-	Blue,
-}
-impl EnemyColor {
-	pub fn random() -> Self {
-		match random!(0..3) {
-			0 => Self::Red,
-			1 => Self::Green,
-			2 => Self::Blue,
-			_ => unreachable!(),
-		}
-	}
-}
+/// Index of a colour in the [`SlimePalette`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EnemyColor(pub usize);
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum EnemyPolarity {
 	Positive,
 	Negative,
 }
 impl EnemyPolarity {
-	pub fn random() -> Self {
-		match random!(0..2) {
+	pub fn random(rng: &mut impl Rng) -> Self {
+		match rng.gen_range(0..2) {
 			0 => Self::Positive,
 			1 => Self::Negative,
 			_ => unreachable!(),
@@ -443,63 +512,76 @@ impl EnemyPolarity {
 
 #[derive(Component, Clone, Copy, Debug)]
 #[component(immutable)]
-#[component(on_insert = on_insert_enemy)]
+#[require(Archetype, AiState)]
 pub struct Enemy {
 	enemy_color: EnemyColor,
 	enemy_polarity: EnemyPolarity,
+	/// How much this slime weighs in the balance, from 1 to [`MAX_MAGNITUDE`]
+	/// for all but bosses.
+	magnitude: u8,
 }
-fn on_insert_enemy(mut world: DeferredWorld, HookContext { entity, .. }: HookContext) {
-	world.commands().queue(move |world: &mut World| {
-		world
-			.run_system_once(
-				move |mut commands: Commands,
-				      mut enemy: Query<(&mut Sprite, &Enemy)>| {
-					let (mut sprite, enemy) = enemy.get_mut(entity).unwrap();
-					sprite.color = (*enemy).into();
-				},
-			)
-			.unwrap();
-	});
-}
+
+pub const MAX_MAGNITUDE: u8 = 3;
 
 impl Enemy {
-	pub fn random() -> Enemy {
+	pub fn random(palette: &SlimePalette, rng: &mut impl Rng) -> Enemy {
 		Enemy {
-			enemy_color: EnemyColor::random(),
-			enemy_polarity: EnemyPolarity::random(),
+			enemy_color: palette.random_color(rng),
+			enemy_polarity: EnemyPolarity::random(rng),
+			magnitude: match rng.gen_range(0.0..1.0) {
+				x if x < 0.6 => 1,
+				x if x < 0.9 => 2,
+				_ => 3,
+			},
 		}
 	}
-}
 
-impl From<Enemy> for Color {
-	fn from(value: Enemy) -> Self {
-		match (value.enemy_color, value.enemy_polarity) {
-			(EnemyColor::Red, EnemyPolarity::Positive) => {
-				Color::from(css::INDIAN_RED).lighter(0.03)
-			}
-			(EnemyColor::Red, EnemyPolarity::Negative) => {
-				Color::from(css::INDIAN_RED).darker(0.16)
-			}
-			(EnemyColor::Blue, EnemyPolarity::Positive) => {
-				Color::from(css::CORNFLOWER_BLUE)
-					.with_saturation(0.98)
-					.lighter(0.08)
-			}
-			(EnemyColor::Blue, EnemyPolarity::Negative) => {
-				Color::from(css::CORNFLOWER_BLUE)
-					.darker(0.20)
-					.with_saturation(0.98)
-			}
-			(EnemyColor::Green, EnemyPolarity::Positive) => {
-				Color::from(css::FOREST_GREEN)
-					.lighter(0.05)
-					.with_saturation(1.01)
-			}
-			(EnemyColor::Green, EnemyPolarity::Negative) => {
-				Color::from(css::FOREST_GREEN).darker(0.05)
-			}
+	/// A slime of the same colour and opposite polarity with a different
+	/// magnitude, so it can only be cancelled out by combining slimes.
+	pub fn counterweight(&self) -> Enemy {
+		self.flipped()
+			.with_magnitude(self.magnitude % MAX_MAGNITUDE + 1)
+	}
+
+	/// The same slime with its polarity swapped.
+	pub fn flipped(&self) -> Enemy {
+		Enemy {
+			enemy_polarity: match self.enemy_polarity {
+				EnemyPolarity::Positive => EnemyPolarity::Negative,
+				EnemyPolarity::Negative => EnemyPolarity::Positive,
+			},
+			..*self
 		}
 	}
+
+	/// The same slime with half the magnitude, rounded up.
+	pub fn halved(&self) -> Enemy {
+		Enemy {
+			magnitude: self.magnitude.div_ceil(2),
+			..*self
+		}
+	}
+
+	pub fn with_magnitude(&self, magnitude: u8) -> Enemy {
+		Enemy {
+			magnitude: magnitude.clamp(1, MAX_MAGNITUDE),
+			..*self
+		}
+	}
+
+	/// What this slime adds to the balance of its colour.
+	pub fn charge(&self) -> i32 {
+		let magnitude = self.magnitude as i32;
+		match self.enemy_polarity {
+			EnemyPolarity::Positive => magnitude,
+			EnemyPolarity::Negative => -magnitude,
+		}
+	}
+
+	/// Heavier slimes are drawn bigger.
+	pub fn sprite_size(&self) -> Vec2 {
+		Vec2::new(32.0, 16.0) * (1.0 + 0.3 * (self.magnitude - 1) as f32)
+	}
 }
 
 #[derive(Component)]
@@ -511,24 +593,41 @@ pub struct Velocity(pub Vec3);
 #[derive(Component)]
 pub struct MaxInternalVelocity(pub f32);
 impl MaxInternalVelocity {
-	pub fn random() -> Self {
-		Self(random!(0.7..1.0) * 1.5)
+	pub fn random(rng: &mut impl Rng) -> Self {
+		Self(rng.gen_range(0.7..1.0) * 1.5)
 	}
 }
 
+/// Slimes go in by [`SlimeId`] rather than query order, which can differ
+/// between a run and its replay.
+fn rebuild_spatial_index(
+	enemies: Query<(Entity, &SlimeId, &Transform), With<Enemy>>,
+	mut spatial_index: ResMut<SpatialIndex>,
+) {
+	let mut enemies: Vec<_> = enemies.iter().collect();
+	enemies.sort_unstable_by_key(|(_, id, _)| **id);
+	spatial_index.rebuild(
+		enemies
+			.into_iter()
+			.map(|(entity, _, transform)| (entity, transform.translation.xy())),
+	);
+}
+
 fn prevent_enemies_from_collision(
-	enemy_positions: Query<(Entity, &GlobalTransform), With<Enemy>>,
+	time: Res<Time>,
+	spatial_index: Res<SpatialIndex>,
 	mut velocities: Query<&mut Velocity>,
 ) {
 	const REPULSION_DISTANCE: f32 = 30.0;
 	const SMALL_REPULSION_DISTANCE: f32 = 20.0;
-	for (e1, p1) in enemy_positions.iter() {
-		for (e2, p2) in enemy_positions.iter() {
+	let ticks = ticks(&time);
+	for (e1, p1) in spatial_index.iter() {
+		for (e2, p2) in spatial_index.within(p1, REPULSION_DISTANCE) {
 			if e2 == e1 {
 				continue;
 			}
 
-			if p1.translation().distance(p2.translation()) < REPULSION_DISTANCE {
+			if p1.distance(p2) < REPULSION_DISTANCE {
 				/*{
 					let dir = p1.translation() - p2.translation();
 					let r = dir.length().max(0.001); // avoid div-by-zero
@@ -549,223 +648,301 @@ fn prevent_enemies_from_collision(
 					velocities.get_mut(e1).unwrap().0 += awa;
 					velocities.get_mut(e2).unwrap().0 -= awa;
 				}*/
-				let awa = (p1.translation() - p2.translation()) / 350.0;
-				let awa = awa.lerp(Vec3::default(), 0.6);
-				velocities.get_mut(e1).unwrap().0 += awa;
-				velocities.get_mut(e2).unwrap().0 -= awa;
+				let awa = (p1 - p2).extend(0.0) / 350.0;
+				let awa = awa.lerp(Vec3::default(), 0.6) * ticks;
+				// The index is from the start of the tick, so a slime may be gone.
+				if let Ok(mut velocity) = velocities.get_mut(e1) {
+					velocity.0 += awa;
+				}
+				if let Ok(mut velocity) = velocities.get_mut(e2) {
+					velocity.0 -= awa;
+				}
 			}
 		}
 	}
 }
 
 fn randomly_change_max_internal_velocity(
-	mut query: Query<&mut MaxInternalVelocity>,
+	time: Res<Time>,
+	mut query: Query<(&SlimeId, &mut MaxInternalVelocity), Without<Boss>>,
 	slime_slowness_level: Res<SlimeSlownessLevel>,
+	current_wave: Res<CurrentWave>,
+	mut rng: ResMut<GameRng>,
 ) {
 	const PER_LEVEL_DECAY: f32 = 0.98;
-	for mut v in query.iter_mut() {
-		if random!(0.0..1.0) < 0.01 {
-			*v = MaxInternalVelocity::random();
+	let chance = 1.0 - 0.99_f32.powf(ticks(&time));
+	let rng = rng.gameplay();
+	// Draw in a fixed order so replays hand out the same speeds.
+	let mut slimes: Vec<_> = query.iter_mut().collect();
+	slimes.sort_unstable_by_key(|(id, _)| **id);
+	for (_, mut v) in slimes {
+		if rng.gen_range(0.0..1.0) < chance {
+			*v = MaxInternalVelocity::random(rng);
+			v.0 *= current_wave.0.speed;
 			v.0 *= PER_LEVEL_DECAY.powi(slime_slowness_level.0 as i32);
 		}
 	}
 }
 
 fn move_enemy(
+	time: Res<Time>,
 	mut enemy: Query<
-		(&mut Velocity, &GlobalTransform, &MaxInternalVelocity),
+		(
+			&mut Velocity,
+			&Transform,
+			&MaxInternalVelocity,
+			&Archetype,
+			Option<&ArchetypeClock>,
+			&AiState,
+		),
 		With<Enemy>,
 	>,
-	player: Single<&GlobalTransform, With<Player>>,
+	player: Single<&Transform, (With<Player>, Without<Enemy>)>,
+	tuning: Res<AiTuning>,
 ) {
-	for (mut e, p, v) in enemy.iter_mut() {
-		let n = player.translation() - p.translation();
-		e.0 = e.0.lerp(n.normalize_or_zero() * v.0, 0.1);
+	let steering = smoothing(0.1, ticks(&time));
+	for (mut e, p, v, archetype, clock, state) in enemy.iter_mut() {
+		let params = tuning.get(*archetype);
+		let (direction, pace) =
+			state.steer(p.translation.xy(), player.translation.xy(), params);
+		let speed = v.0 * archetype.speed_factor(clock) * pace;
+		e.0 = e.0.lerp(direction.extend(0.0) * speed, steering);
 	}
 }
 
-fn move_enemy_2(mut enemy: Query<(&mut Transform, &Velocity)>) {
+/// Velocities are in units per simulation tick.
+fn move_enemy_2(
+	time: Res<Time>,
+	mut enemy: Query<(&mut Transform, &Velocity), With<Enemy>>,
+) {
+	let ticks = ticks(&time);
 	for (mut t, v) in enemy.iter_mut() {
-		t.translation.add_assign(v.0);
+		t.translation.add_assign(v.0 * ticks);
 	}
 }
 
-#[derive(Resource)]
-pub struct LastEntityChained(pub Entity);
+/// The lerp factor that moves as far in `ticks` ticks as `factor` does in one.
+fn smoothing(factor: f32, ticks: f32) -> f32 {
+	1.0 - (1.0 - factor).powf(ticks)
+}
 
-#[derive(Event)]
-pub struct StartChainReaction;
+/// Detonates the first `links` links of the chain at index `chain` in the
+/// [`ChainGraph`], then takes `penalty` off the points it earned.
+#[derive(Event, Clone, Copy)]
+pub struct StartChainReaction {
+	pub chain: usize,
+	pub links: usize,
+	pub penalty: u32,
+}
 
 #[derive(Resource, Default)]
 pub struct Score(u32);
 
-fn draw_chain_balance(
-	mut commands: Commands,
-	chained: Query<&Enemy, With<Chained>>,
-	keyboard: Res<ButtonInput<KeyCode>>,
-	asset_server: Res<AssetServer>,
-	mut start_chain_reaction: EventWriter<StartChainReaction>,
-	score: Res<Score>,
+/// Signed sum of the charges in the active chain for each colour of the
+/// [`SlimePalette`]. Only written when it actually changes, so the HUD can rely
+/// on change detection.
+#[derive(Resource, Default, PartialEq, Debug)]
+pub struct ChainBalance(pub Vec<i32>);
+
+impl ChainBalance {
+	pub fn is_balanced(&self) -> bool {
+		self.0.iter().all(|sum| *sum == 0)
+	}
+}
+
+fn update_chain_balance(
+	chain_graph: Res<ChainGraph>,
+	enemies: Query<&Enemy>,
+	palette: Res<SlimePalette>,
+	mut chain_balance: ResMut<ChainBalance>,
 ) {
-	let mut greens: i32 = 0;
-	let mut reds: i32 = 0;
-	let mut blues: i32 = 0;
-	for enemy in chained.iter() {
-		let mut val = match enemy.enemy_polarity {
-			EnemyPolarity::Positive => 1,
-			EnemyPolarity::Negative => -1,
-		};
-		match enemy.enemy_color {
-			EnemyColor::Red => reds += val,
-			EnemyColor::Green => greens += val,
-			EnemyColor::Blue => blues += val,
+	let mut balance = vec![0; palette.len()];
+	for enemy in enemies.iter_many(chain_graph.chain().links()) {
+		if let Some(sum) = balance.get_mut(enemy.enemy_color.0) {
+			*sum += enemy.charge();
 		}
 	}
+	chain_balance.set_if_neq(ChainBalance(balance));
+}
 
-	let e = commands
-		.spawn((
-			Node {
-				// You can change the `Node` however you want at runtime
-				position_type: PositionType::Absolute,
-				width: Val::Percent(100.0),
-				height: Val::Percent(15.0),
-				align_items: AlignItems::Center,
-				justify_content: JustifyContent::Center,
-				flex_direction: FlexDirection::Column,
-				row_gap: Val::Px(20.0),
-				column_gap: Val::Px(20.0),
-				..default()
-			},
-			children![
-				Text(format!("Score: {}  ", score.0)),
-				Text("Needed to balance: ".to_string()),
-			],
-			Despawn,
-			Transform::from_translation(Vec3::new(0.0, 100.0, 0.0)),
-		))
-		.id();
-	let sub_node = commands
-		.spawn((
-			Node {
-				// You can change the `Node` however you want at runtime
-				position_type: PositionType::Relative,
-				align_items: AlignItems::Center,
-				justify_content: JustifyContent::Center,
-				flex_direction: FlexDirection::Row,
-				row_gap: Val::Px(20.0),
-				column_gap: Val::Px(20.0),
-				..default()
-			},
-			Transform::from_translation(Vec3::new(0.0, -150.0, 0.0)),
-			ChildOf(e),
-		))
-		.id();
-	//commands.entity(e).add_child(sub_node);
-	let e = sub_node;
-	for _ in 0..reds.abs() {
-		let enemy = Enemy {
-			enemy_color: EnemyColor::Red,
-			enemy_polarity: if reds.is_negative() {
-				EnemyPolarity::Positive
-			} else {
-				EnemyPolarity::Negative
-			},
-		};
-		commands.spawn((
-			Node {
-				width: Val::Px(20.0),
-				height: Val::Px(20.0),
-				..default()
-			},
-			BackgroundColor(enemy.into()),
-			ChildOf(e),
-		));
-	}
-	for _ in 0..blues.abs() {
-		let enemy = Enemy {
-			enemy_color: EnemyColor::Blue,
-			enemy_polarity: if blues.is_negative() {
-				EnemyPolarity::Positive
-			} else {
-				EnemyPolarity::Negative
-			},
-		};
-		commands.spawn((
-			Node {
-				width: Val::Px(20.0),
-				height: Val::Px(20.0),
-				..default()
-			},
-			BackgroundColor(enemy.into()),
-			ChildOf(e),
-		));
-	}
-	for _ in 0..greens.abs() {
-		let enemy = Enemy {
-			enemy_color: EnemyColor::Green,
-			enemy_polarity: if greens.is_negative() {
-				EnemyPolarity::Positive
-			} else {
-				EnemyPolarity::Negative
-			},
+fn detonate_on_space(
+	chain_graph: Res<ChainGraph>,
+	enemies: Query<&Enemy>,
+	tick_input: Res<TickInput>,
+	mut start_chain_reaction: EventWriter<StartChainReaction>,
+	mut action_rejected: EventWriter<ActionRejected>,
+	balance: Res<ChainBalance>,
+	partial_detonation: Res<PartialDetonation>,
+) {
+	if tick_input.detonate {
+		let chain = chain_graph.chain();
+		let balanced_prefix = if balance.is_balanced() {
+			chain.len()
+		} else if partial_detonation.enabled {
+			longest_balanced_prefix(chain.links().iter().map(|link| {
+				enemies
+					.get(*link)
+					.ok()
+					.map(|enemy| (enemy.enemy_color, enemy.charge()))
+			}))
+		} else {
+			0
 		};
-		commands.spawn((
-			Node {
-				width: Val::Px(20.0),
-				height: Val::Px(20.0),
-				..default()
-			},
-			BackgroundColor(enemy.into()),
-			ChildOf(e),
-		));
-	}
-	if keyboard.just_pressed(KeyCode::Space) {
-		if reds == 0 && greens == 0 && blues == 0 {
-			start_chain_reaction.write(StartChainReaction);
+		if balanced_prefix > 0 {
+			start_chain_reaction.write(StartChainReaction {
+				chain: chain_graph.active(),
+				links: balanced_prefix,
+				penalty: if balanced_prefix < chain.len() {
+					partial_detonation.penalty
+				} else {
+					0
+				},
+			});
 		} else {
-			commands.spawn(AudioPlayer::new(asset_server.load("audio/error.ogg")));
+			action_rejected.write(ActionRejected);
 		}
 	}
 }
 
+/// Left-clicking a slime queues it to be chained on the next tick, if it is
+/// still in range by then.
 fn on_click_enemy(
 	mut trigger: Trigger<Pointer<Pressed>>,
-	mut chained_enemies: Query<&mut Chained>,
 	mut player_state: Single<&mut PlayerState>,
-	primary_window: Single<&Window, With<PrimaryWindow>>,
-	player: Single<Entity, With<Player>>,
-	enemies: Query<Entity, (With<EnemyClickable>, Without<Chained>)>,
-	mut commands: Commands,
-	mut last_entity_chained: ResMut<LastEntityChained>,
-	asset_server: Res<AssetServer>,
+	primary_window: Option<Single<&Window, With<PrimaryWindow>>>,
+	slimes: Query<&SlimeId>,
+	playback: Res<State<Playback>>,
+	mut pending: ResMut<PendingInput>,
 ) {
+	if trigger.button != PointerButton::Primary || *playback.get() == Playback::Replay {
+		return;
+	}
 	player_state.animation_state = AnimationState::Attack;
-	if let Some(cursor_position) = primary_window.cursor_position() {
-		if cursor_position.x - (primary_window.size().x / 2.0) < 0.0 {
+	// Headless runs have no window, so the player keeps facing the same way.
+	let cursor_offset = primary_window.and_then(|window| {
+		window
+			.cursor_position()
+			.map(|cursor_position| cursor_position.x - window.size().x / 2.0)
+	});
+	if let Some(cursor_offset) = cursor_offset {
+		if cursor_offset < 0.0 {
 			player_state.direction = Direction::Left;
 		}
-		if cursor_position.x - (primary_window.size().x / 2.0) > 0.0 {
+		if cursor_offset > 0.0 {
 			player_state.direction = Direction::Right;
 		}
 	}
-	println!("tried click");
-	let Ok(enemy) = enemies.get(trigger.target) else {
+	let Ok(&slime) = slimes.get(trigger.target) else {
 		return;
 	};
 	trigger.propagate(false);
-	if *player != last_entity_chained.0 {
-		let mut chain = chained_enemies
-			.get_mut(last_entity_chained.0.entity())
-			.unwrap();
-		chain.prev = enemy;
-	}
-	commands.entity(enemy).insert(Chained { prev: *player });
-	commands.entity(enemy).remove::<EnemyClickable>();
-	last_entity_chained.0 = enemy;
-	commands.spawn(AudioPlayer::new(
-		asset_server.load(format!("audio/enemy-attach-{}.ogg", random!(1..5))),
-	));
-	println!("added chain: {}", enemy);
+	pending.0.clicks.push(SlimeClick {
+		slime,
+		button: PointerButton::Primary,
+	});
+}
+
+/// Right-clicking a chained slime cuts the chain there on the next tick,
+/// releasing it and every slime chained after it.
+fn on_right_click_enemy(
+	mut trigger: Trigger<Pointer<Pressed>>,
+	slimes: Query<&SlimeId>,
+	playback: Res<State<Playback>>,
+	mut pending: ResMut<PendingInput>,
+) {
+	if trigger.button != PointerButton::Secondary
+		|| *playback.get() == Playback::Replay
+	{
+		return;
+	}
+	let Ok(&slime) = slimes.get(trigger.target) else {
+		return;
+	};
+	trigger.propagate(false);
+	pending.0.clicks.push(SlimeClick {
+		slime,
+		button: PointerButton::Secondary,
+	});
+}
+
+/// Chains and cuts at the slimes clicked this tick. Clicks on slimes that have
+/// since popped, or left the chain range, do nothing.
+fn apply_clicks(
+	tick_input: Res<TickInput>,
+	slimes: Query<(Entity, &SlimeId)>,
+	clickable: Query<(), (With<EnemyClickable>, Without<Chained>)>,
+	mut commands: Commands,
+	mut chain_graph: ResMut<ChainGraph>,
+	mut slime_chained: EventWriter<SlimeChained>,
+) {
+	for click in &tick_input.clicks {
+		let Some((enemy, _)) = slimes.iter().find(|(_, id)| **id == click.slime) else {
+			continue;
+		};
+		match click.button {
+			PointerButton::Primary => {
+				// The same slime may be clicked twice before the tick runs.
+				if !clickable.contains(enemy) || chain_graph.chain().contains(enemy) {
+					continue;
+				}
+				chain_graph.chain_mut().attach(enemy);
+				commands.entity(enemy).insert(Chained);
+				commands.entity(enemy).remove::<EnemyClickable>();
+				slime_chained.write(SlimeChained {
+					entity: enemy,
+					chain: chain_graph.active(),
+				});
+				println!("added chain: {}", enemy);
+			}
+			PointerButton::Secondary => {
+				let released = chain_graph.cut_at(enemy);
+				release_links(&mut commands, released);
+			}
+			PointerButton::Middle => {}
+		}
+	}
+}
+
+/// `Tab` switches which chain new links are attached to and Space detonates.
+fn cycle_active_chain(
+	tick_input: Res<TickInput>,
+	mut chain_graph: ResMut<ChainGraph>,
+) {
+	if tick_input.cycle_chain {
+		chain_graph.cycle_active();
+	}
+}
+
+/// `Q` undoes the last link of the active chain.
+fn detach_last_link(
+	tick_input: Res<TickInput>,
+	mut chain_graph: ResMut<ChainGraph>,
+	mut commands: Commands,
+	mut action_rejected: EventWriter<ActionRejected>,
+) {
+	if !tick_input.detach {
+		return;
+	}
+	let Some(released) = chain_graph.chain_mut().detach_newest() else {
+		action_rejected.write(ActionRejected);
+		return;
+	};
+	release_links(&mut commands, [released]);
+}
+
+/// Lets go of slimes that have left their chain. Slimes despawned before the
+/// commands apply, say when a run ends mid-detonation, are skipped.
+pub fn release_links(
+	commands: &mut Commands,
+	released: impl IntoIterator<Item = Entity>,
+) {
+	for enemy in released {
+		commands
+			.entity(enemy)
+			.try_remove::<Chained>()
+			.try_insert(EnemyClickable);
+		commands.send_event(SlimeUnchained { entity: enemy });
+	}
 }
 
 #[derive(Component)]
@@ -773,34 +950,43 @@ pub struct EnemyClickable;
 const DISTANCE_FOR_INTERACTION: f32 = 250.0;
 fn enemy_chainable_graphic(
 	mut commands: Commands,
-	enemies: Query<(Entity, &GlobalTransform), With<Enemy>>,
-	player: Single<&GlobalTransform, With<Player>>,
+	spatial_index: Res<SpatialIndex>,
+	clickable: Query<Entity, With<EnemyClickable>>,
+	player: Single<&Transform, With<Player>>,
 	chain_radius: Res<ChainRadiusLevel>,
 ) {
-	for (enemy_entity, enemy_transform) in enemies.iter() {
-		if player.translation().distance(enemy_transform.translation())
-			<= (DISTANCE_FOR_INTERACTION / 2.0 + (chain_radius.0 as f32 * 2.0))
-		{
-			commands.entity(enemy_entity).try_insert(EnemyClickable);
-		} else {
+	let radius = DISTANCE_FOR_INTERACTION / 2.0 + (chain_radius.0 as f32 * 2.0);
+	let in_range: HashSet<Entity> = spatial_index
+		.within(player.translation.xy(), radius)
+		.map(|(entity, _)| entity)
+		.collect();
+	for enemy_entity in clickable.iter() {
+		if !in_range.contains(&enemy_entity) {
 			commands.entity(enemy_entity).remove::<EnemyClickable>();
 		}
 	}
+	for enemy_entity in in_range {
+		if !clickable.contains(enemy_entity) {
+			commands.entity(enemy_entity).try_insert(EnemyClickable);
+		}
+	}
 }
 
 fn on_mouse_no_longer_over_enemy(
 	trigger: Trigger<Pointer<Out>>,
 	mut query: Query<(Entity, &mut Sprite, &Enemy)>,
 	chained: Query<&Chained>,
+	palette: Res<SlimePalette>,
 ) {
 	let (entity, mut sprite, enemy) = query.get_mut(trigger.target()).unwrap();
-	sprite.color = Color::from(*enemy);
+	sprite.color = palette.color_of(enemy);
 }
 
 fn on_mouse_over_enemy(
 	trigger: Trigger<Pointer<Over>>,
 	mut query: Query<(&mut Sprite, &Enemy), With<EnemyClickable>>,
 	chained: Query<&Chained>,
+	palette: Res<SlimePalette>,
 ) {
 	if chained.contains(trigger.target()) {
 		return;
@@ -808,36 +994,26 @@ fn on_mouse_over_enemy(
 	let Ok((mut sprite, enemy)) = query.get_mut(trigger.target()) else {
 		return;
 	};
-	sprite.color = Color::from(*enemy).lighter(0.1);
+	sprite.color = palette.color_of(enemy).lighter(0.1);
 }
 
 const MAP_RADI: Vec2 = Vec2::new(4096.0, 4096.0);
 
 fn move_player(
-	mut player: Single<&mut Transform, With<Player>>,
+	time: Res<Time>,
+	player: Single<(&mut Transform, &mut Velocity), With<Player>>,
 	mut player_state: Single<&mut PlayerState>,
-	keyboard: ResMut<ButtonInput<KeyCode>>,
-	mut velocity: Local<Vec3>,
+	tick_input: Res<TickInput>,
 ) {
+	let (mut player, mut velocity) = player.into_inner();
+	let velocity = &mut velocity.0;
 	let mut player_state: &mut PlayerState = &mut player_state;
 
 	// Acceleration parameter (units per second^2)
 	const ACCELERATION: f32 = 0.1;
 	const SPEED: f32 = 4.0;
 
-	let mut change = Vec2::default();
-	if keyboard.pressed(KeyCode::KeyA) {
-		change.x -= 1.0;
-	}
-	if keyboard.pressed(KeyCode::KeyD) {
-		change.x += 1.0;
-	}
-	if keyboard.pressed(KeyCode::KeyW) {
-		change.y += 1.0;
-	}
-	if keyboard.pressed(KeyCode::KeyS) {
-		change.y -= 1.0;
-	}
+	let change = tick_input.held.direction();
 
 	if change.length() != 0.0 && player_state.animation_state != AnimationState::Attack
 	{
@@ -857,14 +1033,13 @@ fn move_player(
 	let change = change.normalize_or_zero() * SPEED;
 	let change = change.extend(0.0);
 
-	for _ in 0..2 {
-		*velocity = velocity.lerp(change, ACCELERATION);
-	}
+	let ticks = ticks(&time);
+	*velocity = velocity.lerp(change, smoothing(ACCELERATION, 2.0 * ticks));
 	if velocity.distance(change) <= 0.1 {
 		*velocity = change;
 	}
 
-	player.translation += *velocity;
+	player.translation += *velocity * ticks;
 
 	const BUFFER: f32 = 1920.0;
 
@@ -888,72 +1063,25 @@ pub struct ChainAsset(pub Handle<Image>);
 #[derive(Resource)]
 pub struct RadiusCircleAsset(pub MeshMaterial2d<ColorMaterial>, pub Mesh2d);
 
-#[derive(Component)]
-pub struct Chained {
-	prev: Entity,
-}
-
-#[derive(Component)]
-pub struct Despawn;
+#[cfg(test)]
+mod tests {
+	use super::*;
 
-fn draw_chains(
-	mut commands: Commands,
-	chained: Query<(Entity, &Chained)>,
-	positions: Query<&GlobalTransform>,
-	chain_asset: Res<ChainAsset>,
-) {
-	const CHAIN_SIZE: f32 = 12.0 * 1.0;
-	for (entity, chained) in chained.iter() {
-		let Ok(position_1) = positions.get(entity) else {
-			continue;
-		};
-		let Ok(position_2) = positions.get(chained.prev) else {
-			continue;
-		};
-		let delta = position_1.translation() - position_2.translation();
-		// angle in radians around Z‐axis (so sprite “points” from A→B)
-		let angle = delta.y.atan2(delta.x);
-		let distance = position_1.translation().distance(position_2.translation());
-		let temp = (distance / CHAIN_SIZE);
-		let mut distance = (distance / CHAIN_SIZE) as u32;
-		let remainder = temp - distance as f32;
-		let mut flag = false;
-		if distance <= 4 {
-			flag = true;
-			distance = 6;
-		}
-		for chain in 1..distance {
-			let mut chain = position_1
-				.translation()
-				.lerp(position_2.translation(), (chain as f32 / distance as f32));
-			chain.z = 1.0;
-			let t = if flag {
-				Vec3::splat(0.8)
-			} else {
-				Vec3::splat(1.0)
-			};
-			let size = t * ((remainder / distance as f32) + 1.0);
-			commands.spawn((
-				Sprite {
-					image: chain_asset.0.clone(),
-					..default()
-				},
-				Transform::from_translation(chain)
-					.with_rotation(Quat::from_euler(
-						EulerRot::XYZ,
-						0.0,
-						0.0,
-						PI / 2.0 + angle,
-					))
-					.with_scale(size),
-				Despawn,
-			));
+	#[test]
+	fn simulation_runs_without_a_window() {
+		let mut app = headless_app();
+		for _ in 0..5 {
+			app.update();
 		}
-	}
-}
-
-fn despawn(mut commands: Commands, query: Query<Entity, With<Despawn>>) {
-	for entity in query.iter() {
-		commands.entity(entity).despawn();
+		let world = app.world_mut();
+		assert_eq!(
+			world
+				.query_filtered::<Entity, With<Player>>()
+				.iter(world)
+				.count(),
+			1
+		);
+		assert!(world.contains_resource::<ChainGraph>());
+		assert_eq!(world.resource::<Score>().0, 0);
 	}
 }
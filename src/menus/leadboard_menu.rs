@@ -1,3 +1,5 @@
+use crate::daily::Daily;
+use crate::game_mode::GameMode;
 use crate::menus::GameState;
 use crate::theme::widget;
 use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
@@ -43,18 +45,39 @@ fn spawn_menu(
 	let mut score = leaderboard.get_leaderboard();
 	score.sort_by(|awa, uwu| uwu.score.partial_cmp(&awa.score).unwrap());
 
-	for score in score {
+	// Today's daily challenge first, then one section per ranked mode. Older
+	// dailies aren't shown, and scores sent before modes existed were Endless.
+	let daily = Daily::today().category();
+	let mut sections = vec![("Today's Daily".to_string(), daily)];
+	sections.extend(
+		GameMode::ALL
+			.into_iter()
+			.filter(|mode| mode.is_ranked())
+			.map(|mode| (mode.name().to_string(), mode.tag().to_string())),
+	);
+	for (header, tag) in sections {
+		let scores = score.iter().filter(|score| {
+			let meta = score.meta.as_deref().unwrap_or(GameMode::Endless.tag());
+			meta == tag
+		});
 		children.push(
 			commands
-				.spawn((
-					widget::label(format!(
-						"Name: {}, Score: {}, Timestamp: {}",
-						score.player, score.score, score.timestamp
-					)),
-					Pickable::IGNORE,
-				))
+				.spawn((widget::header(header), Pickable::IGNORE))
 				.id(),
 		);
+		for score in scores {
+			children.push(
+				commands
+					.spawn((
+						widget::label(format!(
+							"Name: {}, Score: {}, Timestamp: {}",
+							score.player, score.score, score.timestamp
+						)),
+						Pickable::IGNORE,
+					))
+					.id(),
+			);
+		}
 	}
 	/*let p = commands
 	.spawn((
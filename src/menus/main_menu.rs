@@ -1,4 +1,6 @@
+use crate::daily::{Daily, begin_daily};
 use crate::menus::GameState;
+use crate::replay::{self, ReplayDirectory, begin_replay};
 use crate::theme::widget;
 use bevy::prelude::*;
 use bevy_jornet::Leaderboard;
@@ -20,8 +22,10 @@ fn spawn_main_menu(mut commands: Commands, leaderboard: ResMut<Leaderboard>) {
 		GlobalZIndex(2),
 		StateScoped(GameState::MainMenu),
 		children![
-			widget::button("Play", enter_gameplay),
+			widget::button("Play", open_mode_select),
+			widget::button("Daily", play_daily),
 			widget::button("Tutorial", open_tutorial),
+			widget::button("Watch Last Run", watch_last_run),
 			widget::button("Settings", open_settings_menu),
 			widget::button("Shop", open_shop_menu),
 			widget::button("Leaderboard", open_leaderboard_menu),
@@ -29,11 +33,26 @@ fn spawn_main_menu(mut commands: Commands, leaderboard: ResMut<Leaderboard>) {
 	));
 }
 
-fn enter_gameplay(
+fn open_mode_select(
 	_: Trigger<Pointer<Click>>,
 	mut next_menu: ResMut<NextState<GameState>>,
 ) {
-	next_menu.set(GameState::Game);
+	next_menu.set(GameState::ModeSelect);
+}
+
+fn watch_last_run(_: Trigger<Pointer<Click>>, mut commands: Commands) {
+	commands.queue(|world: &mut World| {
+		let path = world.resource::<ReplayDirectory>().latest();
+		match replay::load(&path) {
+			Ok(recording) => begin_replay(world, recording),
+			Err(error) => warn!("couldn't load {}: {error}", path.display()),
+		}
+	});
+}
+
+/// Everyone gets the same run today, with the same upgrades.
+fn play_daily(_: Trigger<Pointer<Click>>, mut commands: Commands) {
+	commands.queue(|world: &mut World| begin_daily(world, Daily::today()));
 }
 
 fn open_settings_menu(
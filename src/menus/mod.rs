@@ -1,11 +1,13 @@
 mod leadboard_menu;
 mod main_menu;
+mod mode_menu;
 mod pause_menu;
-mod settings_menu;
+pub mod settings_menu;
 pub mod shop_menu;
 
 use crate::menus::leadboard_menu::LeaderboardMenuPlugin;
 use crate::menus::main_menu::MainMenuPlugin;
+use crate::menus::mode_menu::ModeMenuPlugin;
 use crate::menus::pause_menu::PauseMenuPlugin;
 use crate::menus::settings_menu::SettingsMenuPlugin;
 use crate::menus::shop_menu::ShopMenuPlugin;
@@ -17,9 +19,8 @@ pub struct MenuPlugins;
 
 impl Plugin for MenuPlugins {
 	fn build(&self, app: &mut App) {
-		app.init_state::<GameState>();
-		app.init_state::<PauseMenu>();
 		app.add_plugins(MainMenuPlugin);
+		app.add_plugins(ModeMenuPlugin);
 		app.add_plugins(PauseMenuPlugin);
 		app.add_plugins(LeaderboardMenuPlugin);
 		app.add_plugins(SettingsMenuPlugin);
@@ -33,6 +34,7 @@ impl Plugin for MenuPlugins {
 #[states(scoped_entities)]
 pub enum GameState {
 	MainMenu,
+	ModeSelect,
 	Settings,
 	Game,
 	Leaderboard,
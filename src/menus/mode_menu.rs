@@ -0,0 +1,42 @@
+use crate::game_mode::GameMode;
+use crate::menus::GameState;
+use crate::theme::widget;
+use bevy::prelude::*;
+
+pub struct ModeMenuPlugin;
+impl Plugin for ModeMenuPlugin {
+	fn build(&self, app: &mut App) {
+		app.add_systems(OnEnter(GameState::ModeSelect), spawn_mode_menu);
+	}
+}
+
+fn spawn_mode_menu(mut commands: Commands) {
+	commands.spawn((Camera2d, StateScoped(GameState::ModeSelect)));
+	let menu = commands
+		.spawn((
+			widget::ui_root("Mode Select"),
+			GlobalZIndex(2),
+			StateScoped(GameState::ModeSelect),
+		))
+		.id();
+	for mode in GameMode::ALL {
+		commands.spawn((widget::button(mode.name(), play(mode)), ChildOf(menu)));
+		commands.spawn((widget::label(mode.description()), ChildOf(menu)));
+	}
+	commands.spawn((widget::button("Back", main_menu), ChildOf(menu)));
+}
+
+/// Starts a run in `mode`.
+fn play(
+	mode: GameMode,
+) -> impl FnMut(Trigger<Pointer<Click>>, ResMut<GameMode>, ResMut<NextState<GameState>>)
+{
+	move |_, mut game_mode, mut next_menu| {
+		*game_mode = mode;
+		next_menu.set(GameState::Game);
+	}
+}
+
+fn main_menu(_: Trigger<Pointer<Click>>, mut next_menu: ResMut<NextState<GameState>>) {
+	next_menu.set(GameState::MainMenu);
+}
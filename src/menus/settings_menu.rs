@@ -1,5 +1,10 @@
 use crate::Score;
+use crate::chain::PartialDetonation;
+use crate::daily::{DailyRun, end_daily};
+use crate::game_mode::GameMode;
 use crate::menus::GameState;
+use crate::replay::Playback;
+use crate::slime_palette::{MAX_COLORS, MIN_COLORS, SlimePalette};
 use crate::theme::widget;
 use bevy::prelude::*;
 use bevy_jornet::{JornetPlugin, Leaderboard};
@@ -12,12 +17,27 @@ impl Plugin for SettingsMenuPlugin {
 	fn build(&self, app: &mut App) {
 		app.add_systems(OnEnter(GameState::Settings), spawn_menu);
 		app.add_systems(OnExit(GameState::Settings), set_username);
-		app.add_systems(OnExit(GameState::Game), send_score);
+		app.add_systems(
+			OnExit(GameState::Game),
+			send_score.run_if(in_state(Playback::Live)).before(end_daily),
+		);
 	}
 }
 
-fn send_score(leaderboard: ResMut<Leaderboard>, score: ResMut<Score>) {
-	leaderboard.send_score(score.0 as f32);
+/// Scores are tagged with the mode they were played in, and daily runs with
+/// their day's category instead. Zen runs aren't sent at all.
+fn send_score(
+	leaderboard: ResMut<Leaderboard>,
+	score: ResMut<Score>,
+	mode: Res<GameMode>,
+	daily_run: Option<Res<DailyRun>>,
+) {
+	let score = score.0 as f32;
+	if let Some(daily_run) = daily_run {
+		leaderboard.send_score_with_meta(score, &daily_run.daily.category());
+	} else if mode.is_ranked() {
+		leaderboard.send_score_with_meta(score, mode.tag());
+	}
 }
 
 fn set_username(
@@ -32,7 +52,30 @@ fn set_username(
 #[derive(Resource)]
 pub struct Username(pub String);
 
-fn spawn_menu(mut commands: Commands, possible_username: Option<Res<Username>>) {
+/// The most points the settings let a partial detonation cost.
+const MAX_PARTIAL_DETONATION_PENALTY: u32 = 5;
+
+fn partial_detonation_label(partial_detonation: &PartialDetonation) -> String {
+	let state = if partial_detonation.enabled { "on" } else { "off" };
+	format!("Partial detonation: {state} (-{} points)", partial_detonation.penalty)
+}
+
+#[derive(Component)]
+struct PartialDetonationLabel;
+
+#[derive(Component)]
+struct SlimeColorsLabel;
+
+fn slime_colors_label(palette: &SlimePalette) -> String {
+	format!("Slime colours: {}", palette.len())
+}
+
+fn spawn_menu(
+	mut commands: Commands,
+	possible_username: Option<Res<Username>>,
+	partial_detonation: Res<PartialDetonation>,
+	palette: Res<SlimePalette>,
+) {
 	commands.spawn((Camera2d, StateScoped(GameState::Settings)));
 	let text_input = commands
 		.spawn((
@@ -56,12 +99,55 @@ fn spawn_menu(mut commands: Commands, possible_username: Option<Res<Username>>)
 			StateScoped(GameState::Settings),
 			children![
 				widget::button("Main Menu", back),
+				widget::button("Detonation", toggle_partial_detonation),
+				widget::button("Penalty", cycle_partial_detonation_penalty),
+				(
+					widget::label(partial_detonation_label(&partial_detonation)),
+					PartialDetonationLabel
+				),
+				widget::button("Colours", cycle_slime_colors),
+				(widget::label(slime_colors_label(&palette)), SlimeColorsLabel),
 				Text("Username for Leaderboard".to_string()),
 			],
 		))
 		.add_child(text_input);
 }
 
+fn toggle_partial_detonation(
+	_: Trigger<Pointer<Click>>,
+	mut partial_detonation: ResMut<PartialDetonation>,
+	mut label: Single<&mut Text, With<PartialDetonationLabel>>,
+) {
+	partial_detonation.enabled = !partial_detonation.enabled;
+	label.0 = partial_detonation_label(&partial_detonation);
+}
+
+/// Raises the penalty a point at a time, wrapping back round to one.
+fn cycle_partial_detonation_penalty(
+	_: Trigger<Pointer<Click>>,
+	mut partial_detonation: ResMut<PartialDetonation>,
+	mut label: Single<&mut Text, With<PartialDetonationLabel>>,
+) {
+	partial_detonation.penalty =
+		partial_detonation.penalty % MAX_PARTIAL_DETONATION_PENALTY + 1;
+	label.0 = partial_detonation_label(&partial_detonation);
+}
+
+/// Steps through every number of colours on offer, wrapping around.
+fn cycle_slime_colors(
+	_: Trigger<Pointer<Click>>,
+	mut palette: ResMut<SlimePalette>,
+	mut label: Single<&mut Text, With<SlimeColorsLabel>>,
+) {
+	let next = if palette.len() >= MAX_COLORS {
+		MIN_COLORS
+	} else {
+		palette.len() + 1
+	};
+	*palette = SlimePalette::with_colors(next);
+	label.0 = slime_colors_label(&palette);
+}
+
 fn back(_: Trigger<Pointer<Click>>, mut next_menu: ResMut<NextState<GameState>>) {
 	next_menu.set(GameState::MainMenu);
 }
@@ -1,5 +1,7 @@
-use crate::Score;
 use crate::menus::GameState;
+use crate::shop::{
+	ChainRadiusLevel, PurchaseUpgrade, SlimeSlownessLevel, TotalPoints, Upgrade,
+};
 use crate::theme::widget;
 use bevy::prelude::*;
 use bevy_jornet::Leaderboard;
@@ -8,23 +10,10 @@ use bevy_simple_text_input::TextInput;
 pub struct ShopMenuPlugin;
 impl Plugin for ShopMenuPlugin {
 	fn build(&self, app: &mut App) {
-		app.init_resource::<TotalPoints>();
-		app.insert_resource(SlimeSlownessLevel(1));
-		app.insert_resource(ChainRadiusLevel(1));
 		app.add_systems(OnEnter(GameState::Shop), spawn_shop_menu);
-		app.add_systems(OnExit(GameState::Game), add_to_total_points);
-		app.add_systems(OnEnter(GameState::Game), reset_score);
 	}
 }
 
-fn reset_score(mut score: ResMut<Score>) {
-	score.0 = 0;
-}
-
-fn add_to_total_points(score: Res<Score>, mut total_points: ResMut<TotalPoints>) {
-	total_points.0 += score.0 as u64;
-}
-
 fn despawn_these(mut commands: Commands, query: Query<Entity, With<DespawnThese>>) {
 	for entity in query.iter() {
 		commands.entity(entity).despawn();
@@ -35,9 +24,6 @@ fn despawn_these(mut commands: Commands, query: Query<Entity, With<DespawnThese>
 #[derive(Component)]
 struct DespawnThese;
 
-#[derive(Resource, Default)]
-pub struct TotalPoints(pub u64);
-
 #[derive(Component)]
 struct PointsTracker;
 
@@ -85,12 +71,6 @@ fn spawn_shop_menu(
 	commands.entity(menu).add_child(slime_slowness);
 }
 
-#[derive(Resource, Default)]
-pub struct SlimeSlownessLevel(pub(crate) u32);
-
-#[derive(Resource, Default)]
-pub struct ChainRadiusLevel(pub(crate) u32);
-
 #[derive(Component)]
 struct SlimeSlownessButton;
 
@@ -101,31 +81,13 @@ fn open_leaderboard_menu(
 	next_menu.set(GameState::Leaderboard);
 }
 
-fn buy_chain_radius(
-	_: Trigger<Pointer<Click>>,
-	mut commands: Commands,
-	mut chain_radius_level: ResMut<ChainRadiusLevel>,
-	mut total_points: ResMut<TotalPoints>,
-) {
-	if total_points.0 < (chain_radius_level.0 * 3) as u64 {
-		return;
-	}
-	total_points.0 -= (chain_radius_level.0 * 3) as u64;
-	chain_radius_level.0 += 1;
+fn buy_chain_radius(_: Trigger<Pointer<Click>>, mut commands: Commands) {
+	commands.trigger(PurchaseUpgrade(Upgrade::ChainRadius));
 	commands.run_system_cached(despawn_these);
 }
 
-fn buy_slime_slowness(
-	_: Trigger<Pointer<Click>>,
-	mut commands: Commands,
-	mut slime_slowness_level: ResMut<SlimeSlownessLevel>,
-	mut total_points: ResMut<TotalPoints>,
-) {
-	if total_points.0 < (slime_slowness_level.0 * 3) as u64 {
-		return;
-	}
-	total_points.0 -= (slime_slowness_level.0 * 3) as u64;
-	slime_slowness_level.0 += 1;
+fn buy_slime_slowness(_: Trigger<Pointer<Click>>, mut commands: Commands) {
+	commands.trigger(PurchaseUpgrade(Upgrade::SlimeSlowness));
 	commands.run_system_cached(despawn_these);
 }
 
@@ -1,4 +1,6 @@
+use crate::events::BossArrived;
 use crate::menus::GameState;
+use crate::waves::CurrentWave;
 use bevy::audio::Volume;
 use bevy::prelude::*;
 
@@ -7,7 +9,7 @@ impl Plugin for MusicPlugin {
 	fn build(&self, app: &mut App) {
 		app.add_systems(Startup, setup_game_music);
 		app.add_systems(Startup, setup_other_music);
-		app.add_systems(Update, music_controller);
+		app.add_systems(Update, (play_boss_sting, music_controller).chain());
 	}
 }
 
@@ -17,6 +19,11 @@ pub struct NonGameMusic;
 #[derive(Component)]
 pub struct GameMusic;
 
+/// A short sting played once when a boss turns up, ducking the game music
+/// while it lasts.
+#[derive(Component)]
+pub struct BossSting;
+
 fn music_controller(
 	game_state: Res<State<GameState>>,
 	mut non_game_music: Single<
@@ -24,16 +31,40 @@ fn music_controller(
 		(With<NonGameMusic>, Without<GameMusic>),
 	>,
 	mut game_music: Single<&mut AudioSink, With<GameMusic>>,
+	current_wave: Res<CurrentWave>,
+	boss_sting: Query<(), With<BossSting>>,
 ) {
 	if game_state.get() == &GameState::Game {
+		// The game music swells and quickens as the waves get harder.
+		let intensity = current_wave.0.intensity;
+		let volume = 0.15 + 0.1 * intensity;
+		let ducked = if boss_sting.is_empty() { 1.0 } else { 0.3 };
 		non_game_music.set_volume(Volume::Linear(0.001));
-		game_music.set_volume(Volume::Linear(0.15));
+		game_music.set_volume(Volume::Linear(volume * ducked));
+		game_music.set_speed(1.0 + 0.1 * intensity);
 	} else {
 		game_music.set_volume(Volume::Linear(0.001));
+		game_music.set_speed(1.0);
 		non_game_music.set_volume(Volume::Linear(0.15));
 	}
 }
 
+fn play_boss_sting(
+	mut commands: Commands,
+	mut arrived: EventReader<BossArrived>,
+	stings: Query<(), With<BossSting>>,
+	asset_server: Res<AssetServer>,
+) {
+	if arrived.read().count() > 0 && stings.is_empty() {
+		commands.spawn((
+			AudioPlayer::new(asset_server.load("audio/boss-sting.ogg")),
+			PlaybackSettings::DESPAWN.with_volume(Volume::Linear(0.4)),
+			BossSting,
+			StateScoped(GameState::Game),
+		));
+	}
+}
+
 fn setup_other_music(mut commands: Commands, asset_server: Res<AssetServer>) {
 	commands.spawn((
 		AudioPlayer::new(asset_server.load("audio/music/Overworld.ogg")),
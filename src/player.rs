@@ -1,49 +1,73 @@
 use crate::menus::GameState;
-use crate::{Enemy, LastEntityChained, Player};
+use crate::chain::ChainGraph;
+use crate::events::PlayerHit;
+use crate::game_mode::GameMode;
+use crate::interpolation::Interpolated;
+use crate::spatial::SpatialIndex;
+use crate::{Enemy, Player, Simulation, Velocity};
 use bevy::color::palettes::css;
 use bevy::math::bounding::{Aabb2d, BoundingVolume, IntersectsVolume};
 use bevy::prelude::*;
-use bevy::render::primitives::Aabb;
 use bevy_defer::{AsyncAccess, AsyncCommandsExtension, AsyncWorld};
 use std::ops::Mul;
 
 pub struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
+	fn build(&self, app: &mut App) {
+		app.add_systems(OnEnter(GameState::Game), setup_player);
+		app.add_systems(FixedUpdate, handle_hit.in_set(Simulation::Outcome));
+	}
+}
+
+/// The player's sprite and its animation.
+pub struct PlayerVisualsPlugin;
+impl Plugin for PlayerVisualsPlugin {
 	fn build(&self, app: &mut App) {
 		app.add_systems(
 			OnEnter(GameState::Game),
 			(
 				setup_player_spritesheet,
-				setup_player,
+				dress_player,
 				player_animation_player,
 			)
-				.chain(),
+				.chain()
+				.after(setup_player),
 		);
-		app.add_systems(Update, handle_hit);
 	}
 }
 
+/// Slimes further than this from the player can't be touching them.
+const HIT_CHECK_RADIUS: f32 = 128.0;
+
+/// Half the size the player is drawn at.
+const PLAYER_HALF_SIZE: Vec2 = Vec2::splat(32.0);
+
 fn handle_hit(
-	player: Single<(&Aabb, &Transform), With<Player>>,
-	slimes: Query<(&Aabb, &Transform), With<Enemy>>,
-	mut gizmos: Gizmos,
+	player: Single<&Transform, With<Player>>,
+	spatial_index: Res<SpatialIndex>,
+	slimes: Query<(Entity, &Enemy, &Transform)>,
 	mut game_state: ResMut<NextState<GameState>>,
-	asset_server: Res<AssetServer>,
-	mut commands: Commands,
+	mut player_hit: EventWriter<PlayerHit>,
+	mode: Res<GameMode>,
 ) {
-	let mut player_aabb =
-		Aabb2d::new(player.0.center.xy(), player.0.half_extents.xy() / 3.5);
-	player_aabb.translate_by(player.1.translation.xy());
+	if !mode.player_can_die() {
+		return;
+	}
+	// Hitboxes come from the sprite sizes rather than the rendered bounds, so
+	// this works without a renderer.
+	let mut player_aabb = Aabb2d::new(Vec2::ZERO, PLAYER_HALF_SIZE / 3.5);
+	player_aabb.translate_by(player.translation.xy());
 	/*gizmos.rect_2d(
 		Isometry2d::new(player_aabb.center(), Rot2::default()),
 		player_aabb.half_size().mul(Vec2::splat(2.0)),
 		css::FOREST_GREEN,
 	);*/
-	for (slime_aabb, slime_transform) in slimes.iter() {
-		let mut slime_aabb = Aabb2d::new(
-			slime_aabb.center.xy(),
-			slime_aabb.half_extents.xy() * Vec2::new(0.45, 0.8),
-		);
+	let nearby = spatial_index
+		.within(player.translation.xy(), HIT_CHECK_RADIUS)
+		.map(|(entity, _)| entity);
+	for (slime, enemy, slime_transform) in slimes.iter_many(nearby) {
+		let mut slime_aabb =
+			Aabb2d::new(Vec2::ZERO, enemy.sprite_size() / 2.0 * Vec2::new(0.45, 0.8));
 		slime_aabb.translate_by(slime_transform.translation.xy());
 		/*gizmos.rect_2d(
 			Isometry2d::new(slime_aabb.center(), Rot2::default()),
@@ -52,7 +76,7 @@ fn handle_hit(
 		);*/
 		if slime_aabb.intersects(&player_aabb) {
 			game_state.set(GameState::Shop);
-			commands.spawn(AudioPlayer::new(asset_server.load("audio/die.ogg")));
+			player_hit.write(PlayerHit { slime });
 		}
 	}
 }
@@ -161,16 +185,18 @@ pub struct PlayerSpriteSheet {
 	pub run: Sprite,
 }
 
-fn setup_player(mut commands: Commands, player_sprite_sheet: Res<PlayerSpriteSheet>) {
+fn setup_player(mut commands: Commands) {
+	let translation = Vec3::new(-100.0, 30.0, 0.0);
 	let e = commands
 		.spawn((
 			PlayerState {
 				animation_state: AnimationState::Idle,
 				direction: Direction::Right,
 			},
-			Transform::from_translation(Vec3::new(-100.0, 30.0, 0.0)),
+			Transform::from_translation(translation),
+			Interpolated::at(translation),
+			Velocity(Vec3::ZERO),
 			Player,
-			player_sprite_sheet.idle.clone(),
 			StateScoped(GameState::Game),
 		))
 		.id();
@@ -183,10 +209,18 @@ fn setup_player(mut commands: Commands, player_sprite_sheet: Res<PlayerSpriteShe
 		Transform::from_translation(Vec3::new(0.0, 0.0, -5.0)),
 	))
 	.id();*/
-	commands.insert_resource(LastEntityChained(e));
+	commands.insert_resource(ChainGraph::new(e));
 	//commands.entity(e).add_child(child);
 }
 
+fn dress_player(
+	mut commands: Commands,
+	player: Single<Entity, With<Player>>,
+	player_sprite_sheet: Res<PlayerSpriteSheet>,
+) {
+	commands.entity(*player).insert(player_sprite_sheet.idle.clone());
+}
+
 fn setup_player_spritesheet(
 	mut commands: Commands,
 	asset_server: Res<AssetServer>,
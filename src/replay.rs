@@ -0,0 +1,515 @@
+//! Every run is recorded as its seed, the upgrades and settings it was played
+//! with and the [`TickInput`] of each tick. Playing a [`Recording`] back feeds
+//! that input to the simulation instead of the keyboard and mouse, so the run
+//! plays out exactly as it did.
+
+use crate::Simulation;
+use crate::chain::PartialDetonation;
+use crate::enemy::SpawnView;
+use crate::game_mode::GameMode;
+use crate::game_rng::{GameRng, RunSeed, seed_run};
+use crate::interpolation::SIMULATION_HZ;
+use crate::menus::{GameState, PauseMenu};
+use crate::shop::{ChainRadiusLevel, SlimeSlownessLevel};
+use crate::slime_palette::{MAX_COLORS, SlimePalette};
+use crate::tick_input::{HeldKeys, SlimeClick, SlimeId, TickInput, take_tick_input};
+use bevy::prelude::*;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub struct ReplayPlugin;
+impl Plugin for ReplayPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_state::<Playback>();
+		app.init_resource::<Recording>();
+		app.add_systems(OnEnter(GameState::Game), start_recording.after(seed_run));
+		app.add_systems(FixedUpdate, record_tick.in_set(Simulation::Input));
+		app.add_systems(
+			FixedPreUpdate,
+			feed_replay
+				.after(take_tick_input)
+				.run_if(in_state(GameState::Game))
+				.run_if(in_state(PauseMenu::Unpaused))
+				.run_if(in_state(Playback::Replay)),
+		);
+		app.add_systems(
+			OnExit(GameState::Game),
+			(
+				save_recording
+					.run_if(in_state(Playback::Live))
+					.run_if(resource_exists::<ReplayDirectory>),
+				end_replay.run_if(in_state(Playback::Replay)),
+			),
+		);
+	}
+}
+
+/// Whether the current run is being played or watched.
+#[derive(States, Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub enum Playback {
+	#[default]
+	Live,
+	Replay,
+}
+
+/// Where finished runs are saved. Without it nothing is written.
+#[derive(Resource, Clone, Debug)]
+pub struct ReplayDirectory(pub PathBuf);
+
+impl ReplayDirectory {
+	/// The most recent run is always saved here as well.
+	pub fn latest(&self) -> PathBuf {
+		self.0.join("latest.replay")
+	}
+}
+
+impl Default for ReplayDirectory {
+	fn default() -> Self {
+		Self(PathBuf::from("replays"))
+	}
+}
+
+/// Everything besides input that changes how a run plays out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Conditions {
+	pub chain_radius_level: u32,
+	pub slime_slowness_level: u32,
+	pub partial_detonation: bool,
+	pub partial_detonation_penalty: u32,
+	pub mode: GameMode,
+	/// The [`SpawnView`] clusters were kept out of.
+	pub view: UVec2,
+	/// How many colours of the [`SlimePalette`] were in play.
+	pub colors: u8,
+}
+
+impl Conditions {
+	pub fn of(world: &World) -> Self {
+		let partial_detonation = world.resource::<PartialDetonation>();
+		Self {
+			chain_radius_level: world.resource::<ChainRadiusLevel>().0,
+			slime_slowness_level: world.resource::<SlimeSlownessLevel>().0,
+			partial_detonation: partial_detonation.enabled,
+			partial_detonation_penalty: partial_detonation.penalty,
+			mode: *world.resource::<GameMode>(),
+			view: world.resource::<SpawnView>().0,
+			colors: world.resource::<SlimePalette>().len() as u8,
+		}
+	}
+
+	pub fn apply(&self, world: &mut World) {
+		world.resource_mut::<ChainRadiusLevel>().0 = self.chain_radius_level;
+		world.resource_mut::<SlimeSlownessLevel>().0 = self.slime_slowness_level;
+		let mut partial_detonation = world.resource_mut::<PartialDetonation>();
+		partial_detonation.enabled = self.partial_detonation;
+		partial_detonation.penalty = self.partial_detonation_penalty;
+		*world.resource_mut::<GameMode>() = self.mode;
+		world.resource_mut::<SpawnView>().0 = self.view;
+		world.insert_resource(SlimePalette::with_colors(self.colors.into()));
+	}
+}
+
+/// A run as it was played, or the current one as far as it has got.
+#[derive(Resource, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recording {
+	pub seed: u64,
+	pub conditions: Conditions,
+	pub ticks: Vec<TickInput>,
+}
+
+/// The recording being watched, and the conditions to put back afterwards.
+#[derive(Resource)]
+struct ReplayPlayer {
+	recording: Recording,
+	next_tick: usize,
+	restore: Conditions,
+}
+
+/// Starts watching `recording` as a new run.
+pub fn begin_replay(world: &mut World, recording: Recording) {
+	let restore = Conditions::of(world);
+	recording.conditions.apply(world);
+	world.resource_mut::<RunSeed>().0 = Some(recording.seed);
+	world.insert_resource(ReplayPlayer {
+		recording,
+		next_tick: 0,
+		restore,
+	});
+	world
+		.resource_mut::<NextState<Playback>>()
+		.set(Playback::Replay);
+	// A headless app is already about to enter the game.
+	if *world.resource::<State<GameState>>().get() != GameState::Game {
+		world
+			.resource_mut::<NextState<GameState>>()
+			.set(GameState::Game);
+	}
+}
+
+fn start_recording(world: &mut World) {
+	let recording = Recording {
+		seed: world.resource::<GameRng>().seed(),
+		conditions: Conditions::of(world),
+		ticks: Vec::new(),
+	};
+	world.insert_resource(recording);
+}
+
+fn record_tick(tick_input: Res<TickInput>, mut recording: ResMut<Recording>) {
+	recording.ticks.push(tick_input.clone());
+}
+
+/// Replaces this tick's input with the recorded one. The run ends where the
+/// recording does.
+fn feed_replay(
+	mut player: ResMut<ReplayPlayer>,
+	mut tick_input: ResMut<TickInput>,
+	mut game_state: ResMut<NextState<GameState>>,
+) {
+	let Some(recorded) = player.recording.ticks.get(player.next_tick) else {
+		*tick_input = TickInput::default();
+		game_state.set(GameState::MainMenu);
+		return;
+	};
+	*tick_input = recorded.clone();
+	player.next_tick += 1;
+}
+
+fn end_replay(world: &mut World) {
+	if let Some(player) = world.remove_resource::<ReplayPlayer>() {
+		player.restore.apply(world);
+	}
+	world
+		.resource_mut::<NextState<Playback>>()
+		.set(Playback::Live);
+}
+
+fn save_recording(recording: Res<Recording>, directory: Res<ReplayDirectory>) {
+	let bytes = recording.encode();
+	let named = directory.0.join(format!("{:016x}.replay", recording.seed));
+	let saved = fs::create_dir_all(&directory.0)
+		.and_then(|()| fs::write(&named, &bytes))
+		.and_then(|()| fs::write(directory.latest(), &bytes));
+	if let Err(error) = saved {
+		warn!("couldn't save the replay to {}: {error}", named.display());
+	}
+}
+
+pub fn load(path: &Path) -> Result<Recording, ReplayError> {
+	Recording::decode(&fs::read(path)?)
+}
+
+/// The file passed as `--replay <file>`, if any.
+pub fn path_from_args() -> Option<PathBuf> {
+	let mut args = std::env::args().skip_while(|arg| arg != "--replay");
+	args.next()?;
+	args.next().map(PathBuf::from)
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+	Io(io::Error),
+	NotAReplay,
+	UnsupportedVersion(u8),
+	Truncated,
+	Corrupt,
+}
+
+impl fmt::Display for ReplayError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			ReplayError::Io(error) => error.fmt(f),
+			ReplayError::NotAReplay => write!(f, "not a replay file"),
+			ReplayError::UnsupportedVersion(version) => {
+				write!(f, "replay format version {version} is not supported")
+			}
+			ReplayError::Truncated => write!(f, "the replay ends early"),
+			ReplayError::Corrupt => write!(f, "the replay is corrupt"),
+		}
+	}
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+	fn from(error: io::Error) -> Self {
+		ReplayError::Io(error)
+	}
+}
+
+const MAGIC: &[u8; 4] = b"J6RP";
+const VERSION: u8 = 1;
+
+/// A day of ticks. No real run gets near it, so a file that claims more is
+/// corrupt rather than something to allocate for.
+const MAX_TICKS: u64 = 24 * 60 * 60 * SIMULATION_HZ as u64;
+
+const DETONATE: u8 = 1 << 4;
+const DETACH: u8 = 1 << 5;
+const CYCLE_CHAIN: u8 = 1 << 6;
+const CLICKS: u8 = 1 << 7;
+
+impl Recording {
+	/// A header followed by runs of identical ticks, each stored once with how
+	/// many times it repeats. Holding the same keys for a while costs a few
+	/// bytes; clicks are stored by [`SlimeId`].
+	pub fn encode(&self) -> Vec<u8> {
+		let mut bytes = MAGIC.to_vec();
+		bytes.push(VERSION);
+		bytes.extend(self.seed.to_le_bytes());
+		let conditions = &self.conditions;
+		write_varint(&mut bytes, conditions.chain_radius_level.into());
+		write_varint(&mut bytes, conditions.slime_slowness_level.into());
+		bytes.push(conditions.partial_detonation as u8);
+		write_varint(&mut bytes, conditions.partial_detonation_penalty.into());
+		bytes.push(conditions.mode as u8);
+		write_varint(&mut bytes, conditions.view.x.into());
+		write_varint(&mut bytes, conditions.view.y.into());
+		bytes.push(conditions.colors);
+		for run in self.ticks.chunk_by(|a, b| a == b) {
+			write_varint(&mut bytes, run.len() as u64);
+			encode_tick(&mut bytes, &run[0]);
+		}
+		bytes
+	}
+
+	pub fn decode(bytes: &[u8]) -> Result<Self, ReplayError> {
+		let mut reader = Reader(bytes);
+		if reader.take(MAGIC.len())? != MAGIC {
+			return Err(ReplayError::NotAReplay);
+		}
+		let version = reader.byte()?;
+		if version != VERSION {
+			return Err(ReplayError::UnsupportedVersion(version));
+		}
+		let seed = u64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+		let conditions = Conditions {
+			chain_radius_level: reader.varint_u32()?,
+			slime_slowness_level: reader.varint_u32()?,
+			partial_detonation: reader.byte()? != 0,
+			partial_detonation_penalty: reader.varint_u32()?,
+			mode: *GameMode::ALL
+				.get(reader.byte()? as usize)
+				.ok_or(ReplayError::Corrupt)?,
+			view: UVec2::new(reader.varint_u32()?, reader.varint_u32()?),
+			colors: reader.byte()?,
+		};
+		if conditions.colors == 0 || conditions.colors as usize > MAX_COLORS {
+			return Err(ReplayError::Corrupt);
+		}
+		let mut ticks = Vec::new();
+		while !reader.0.is_empty() {
+			let repeats = reader.varint()?;
+			if repeats > MAX_TICKS - ticks.len() as u64 {
+				return Err(ReplayError::Corrupt);
+			}
+			let tick = decode_tick(&mut reader)?;
+			ticks.extend(std::iter::repeat_n(tick, repeats as usize));
+		}
+		Ok(Self {
+			seed,
+			conditions,
+			ticks,
+		})
+	}
+}
+
+fn encode_tick(bytes: &mut Vec<u8>, tick: &TickInput) {
+	let mut flags = tick.held.0;
+	for (set, flag) in [
+		(tick.detonate, DETONATE),
+		(tick.detach, DETACH),
+		(tick.cycle_chain, CYCLE_CHAIN),
+		(!tick.clicks.is_empty(), CLICKS),
+	] {
+		if set {
+			flags |= flag;
+		}
+	}
+	bytes.push(flags);
+	if tick.clicks.is_empty() {
+		return;
+	}
+	write_varint(bytes, tick.clicks.len() as u64);
+	for click in &tick.clicks {
+		write_varint(bytes, click.slime.0.into());
+		bytes.push(match click.button {
+			PointerButton::Primary => 0,
+			PointerButton::Secondary => 1,
+			PointerButton::Middle => 2,
+		});
+	}
+}
+
+fn decode_tick(reader: &mut Reader) -> Result<TickInput, ReplayError> {
+	let flags = reader.byte()?;
+	let mut tick = TickInput {
+		held: HeldKeys(flags & 0b1111),
+		detonate: flags & DETONATE != 0,
+		detach: flags & DETACH != 0,
+		cycle_chain: flags & CYCLE_CHAIN != 0,
+		clicks: Vec::new(),
+	};
+	if flags & CLICKS == 0 {
+		return Ok(tick);
+	}
+	for _ in 0..reader.varint()? {
+		let slime = SlimeId(reader.varint_u32()?);
+		let button = match reader.byte()? {
+			0 => PointerButton::Primary,
+			1 => PointerButton::Secondary,
+			2 => PointerButton::Middle,
+			_ => return Err(ReplayError::Corrupt),
+		};
+		tick.clicks.push(SlimeClick { slime, button });
+	}
+	Ok(tick)
+}
+
+/// Seven bits at a time, lowest first, with the top bit set on all but the
+/// last byte.
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+	while value >= 0x80 {
+		bytes.push(value as u8 | 0x80);
+		value >>= 7;
+	}
+	bytes.push(value as u8);
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+	fn take(&mut self, len: usize) -> Result<&'a [u8], ReplayError> {
+		if self.0.len() < len {
+			return Err(ReplayError::Truncated);
+		}
+		let (taken, rest) = self.0.split_at(len);
+		self.0 = rest;
+		Ok(taken)
+	}
+
+	fn byte(&mut self) -> Result<u8, ReplayError> {
+		Ok(self.take(1)?[0])
+	}
+
+	fn varint(&mut self) -> Result<u64, ReplayError> {
+		let mut value = 0;
+		for shift in (0..64).step_by(7) {
+			let byte = self.byte()?;
+			value |= ((byte & 0x7f) as u64) << shift;
+			if byte & 0x80 == 0 {
+				return Ok(value);
+			}
+		}
+		Err(ReplayError::Corrupt)
+	}
+
+	fn varint_u32(&mut self) -> Result<u32, ReplayError> {
+		self.varint()?.try_into().map_err(|_| ReplayError::Corrupt)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn recording() -> Recording {
+		let walk = TickInput {
+			held: HeldKeys(HeldKeys::UP | HeldKeys::RIGHT),
+			..default()
+		};
+		let click = TickInput {
+			detonate: true,
+			clicks: vec![
+				SlimeClick {
+					slime: SlimeId(300),
+					button: PointerButton::Primary,
+				},
+				SlimeClick {
+					slime: SlimeId(2),
+					button: PointerButton::Secondary,
+				},
+			],
+			..walk.clone()
+		};
+		let mut ticks = vec![TickInput::default(); 40];
+		ticks.extend(vec![walk.clone(); 1000]);
+		ticks.push(click);
+		ticks.push(walk);
+		Recording {
+			seed: 0xdead_beef_cafe,
+			conditions: Conditions {
+				chain_radius_level: 3,
+				slime_slowness_level: 1,
+				partial_detonation: true,
+				partial_detonation_penalty: 3,
+				mode: GameMode::SurvivalWaves,
+				view: UVec2::new(1920, 1080),
+				colors: 4,
+			},
+			ticks,
+		}
+	}
+
+	#[test]
+	fn recordings_survive_a_round_trip() {
+		let recording = recording();
+		let bytes = recording.encode();
+		assert_eq!(Recording::decode(&bytes).unwrap(), recording);
+		// Four runs of ticks, most of them one repeated input.
+		assert!(bytes.len() < 40, "{} bytes", bytes.len());
+	}
+
+	#[test]
+	fn damaged_files_are_rejected() {
+		let bytes = recording().encode();
+		assert!(matches!(
+			Recording::decode(b"nope"),
+			Err(ReplayError::NotAReplay)
+		));
+		assert!(matches!(
+			Recording::decode(&bytes[..bytes.len() - 1]),
+			Err(ReplayError::Truncated)
+		));
+		let mut newer = bytes.clone();
+		newer[MAGIC.len()] = VERSION + 1;
+		assert!(matches!(
+			Recording::decode(&newer),
+			Err(ReplayError::UnsupportedVersion(_))
+		));
+		let mut colorless = recording();
+		colorless.conditions.colors = 0;
+		assert!(matches!(
+			Recording::decode(&colorless.encode()),
+			Err(ReplayError::Corrupt)
+		));
+	}
+
+	#[test]
+	fn endless_runs_of_ticks_are_rejected() {
+		let header = Recording {
+			ticks: Vec::new(),
+			..recording()
+		};
+		let mut bytes = header.encode();
+		write_varint(&mut bytes, u64::MAX);
+		encode_tick(&mut bytes, &TickInput::default());
+		assert!(matches!(
+			Recording::decode(&bytes),
+			Err(ReplayError::Corrupt)
+		));
+
+		// Runs that are fine on their own can't add up past the limit either.
+		let mut bytes = header.encode();
+		for repeats in [10, MAX_TICKS - 5] {
+			write_varint(&mut bytes, repeats);
+			encode_tick(&mut bytes, &TickInput::default());
+		}
+		assert!(matches!(
+			Recording::decode(&bytes),
+			Err(ReplayError::Corrupt)
+		));
+	}
+}
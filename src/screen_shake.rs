@@ -1,7 +1,8 @@
+use crate::events::SlimePopped;
+use crate::game_rng::GameRng;
 use crate::menus::GameState;
 use bevy::prelude::*;
-use rand::{Rng, SeedableRng};
-use rand_chacha::ChaCha8Rng;
+use rand::Rng;
 
 const CAMERA_DECAY_RATE: f32 = 0.95; // Adjust this for smoother or snappier decay
 const TRAUMA_DECAY_SPEED: f32 = 1.2; // How fast trauma decays
@@ -14,7 +15,6 @@ const MAX_OFFSET: f32 = 800.0;
 pub struct ScreenShakePlugin;
 impl Plugin for ScreenShakePlugin {
 	fn build(&self, app: &mut App) {
-		app.add_event::<SlimeDestroyed>();
 		app.init_resource::<ScreenShake>();
 		app.add_systems(
 			Update,
@@ -56,15 +56,12 @@ impl ScreenShake {
 	}
 }
 
-#[derive(Event)]
-pub struct SlimeDestroyed;
-
 fn trigger_shake_on_space(
 	time: Res<Time>,
-	slime_destroyed: EventReader<SlimeDestroyed>,
+	slime_popped: EventReader<SlimePopped>,
 	mut screen_shake: ResMut<ScreenShake>,
 ) {
-	if !slime_destroyed.is_empty() {
+	if !slime_popped.is_empty() {
 		let screen_shake_clone = screen_shake.clone();
 		screen_shake.start_shake(
 			MAX_ANGLE,
@@ -79,8 +76,9 @@ fn screen_shake(
 	time: Res<Time>,
 	mut screen_shake: ResMut<ScreenShake>,
 	mut query: Query<(&mut Camera, &mut Transform)>,
+	mut rng: ResMut<GameRng>,
 ) {
-	let mut rng = ChaCha8Rng::from_entropy();
+	let rng = rng.cosmetic();
 	let shake = screen_shake.trauma * screen_shake.trauma;
 	let angle =
 		(screen_shake.max_angle * shake).to_radians() * rng.gen_range(-1.0..1.0);
@@ -0,0 +1,80 @@
+//! Sound effects for gameplay events.
+
+use crate::events::{
+	ActionRejected, PlayerHit, SlimeChained, SlimePopped, SlimeUnchained,
+};
+use crate::game_rng::GameRng;
+use bevy::audio::Volume;
+use bevy::prelude::*;
+use rand::Rng;
+
+pub struct SfxPlugin;
+impl Plugin for SfxPlugin {
+	fn build(&self, app: &mut App) {
+		app.add_systems(
+			Update,
+			(
+				play_chain_sounds,
+				play_pop_sounds,
+				play_hit_sound,
+				play_error_sound,
+			),
+		);
+	}
+}
+
+fn play_chain_sounds(
+	mut commands: Commands,
+	mut chained: EventReader<SlimeChained>,
+	mut unchained: EventReader<SlimeUnchained>,
+	asset_server: Res<AssetServer>,
+	mut rng: ResMut<GameRng>,
+) {
+	let count = chained.read().count() + unchained.read().count();
+	if count == 0 {
+		return;
+	}
+	commands.spawn(AudioPlayer::new(
+		asset_server.load(format!(
+			"audio/enemy-attach-{}.ogg",
+			rng.cosmetic().gen_range(1..5)
+		)),
+	));
+}
+
+fn play_pop_sounds(
+	mut commands: Commands,
+	mut popped: EventReader<SlimePopped>,
+	asset_server: Res<AssetServer>,
+) {
+	for SlimePopped { combo_index, .. } in popped.read() {
+		// The combo goes 1, 3, 5, ... so this is the pop's place in the reaction.
+		let i = (combo_index.saturating_sub(1) / 2) as f32;
+		commands.spawn((
+			AudioPlayer::new(asset_server.load("audio/slime-squish.ogg")),
+			PlaybackSettings::ONCE
+				.with_speed(0.9 / (3.0 / 1.1_f32.powf(i)).max(0.3))
+				.with_volume(Volume::Linear(5.5)),
+		));
+	}
+}
+
+fn play_hit_sound(
+	mut commands: Commands,
+	mut hits: EventReader<PlayerHit>,
+	asset_server: Res<AssetServer>,
+) {
+	if hits.read().count() > 0 {
+		commands.spawn(AudioPlayer::new(asset_server.load("audio/die.ogg")));
+	}
+}
+
+fn play_error_sound(
+	mut commands: Commands,
+	mut rejected: EventReader<ActionRejected>,
+	asset_server: Res<AssetServer>,
+) {
+	if rejected.read().count() > 0 {
+		commands.spawn(AudioPlayer::new(asset_server.load("audio/error.ogg")));
+	}
+}
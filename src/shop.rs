@@ -0,0 +1,65 @@
+//! Points carried between runs and the upgrades they buy. The shop menu only
+//! shows these and triggers [`PurchaseUpgrade`].
+
+use crate::Score;
+use crate::menus::GameState;
+use crate::replay::Playback;
+use bevy::prelude::*;
+
+pub struct ShopPlugin;
+impl Plugin for ShopPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<TotalPoints>();
+		app.insert_resource(SlimeSlownessLevel(1));
+		app.insert_resource(ChainRadiusLevel(1));
+		app.add_observer(purchase_upgrade);
+		// Watching a replay earns nothing.
+		app.add_systems(
+			OnExit(GameState::Game),
+			add_to_total_points.run_if(in_state(Playback::Live)),
+		);
+	}
+}
+
+/// Points earned over every run, spent in the shop.
+#[derive(Resource, Default)]
+pub struct TotalPoints(pub u64);
+
+#[derive(Resource, Default)]
+pub struct SlimeSlownessLevel(pub(crate) u32);
+
+#[derive(Resource, Default)]
+pub struct ChainRadiusLevel(pub(crate) u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Upgrade {
+	ChainRadius,
+	SlimeSlowness,
+}
+
+/// Buys the next level of an upgrade if there are enough [`TotalPoints`].
+/// Each level costs three times the current one.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct PurchaseUpgrade(pub Upgrade);
+
+fn purchase_upgrade(
+	trigger: Trigger<PurchaseUpgrade>,
+	mut total_points: ResMut<TotalPoints>,
+	mut chain_radius_level: ResMut<ChainRadiusLevel>,
+	mut slime_slowness_level: ResMut<SlimeSlownessLevel>,
+) {
+	let level = match trigger.0 {
+		Upgrade::ChainRadius => &mut chain_radius_level.0,
+		Upgrade::SlimeSlowness => &mut slime_slowness_level.0,
+	};
+	let price = (*level * 3) as u64;
+	if total_points.0 < price {
+		return;
+	}
+	total_points.0 -= price;
+	*level += 1;
+}
+
+fn add_to_total_points(score: Res<Score>, mut total_points: ResMut<TotalPoints>) {
+	total_points.0 += score.0 as u64;
+}
@@ -0,0 +1,116 @@
+//! The set of slime colours in play. Balancing, spawning and every place that
+//! tints a slime read from here, so a run can use a different number of
+//! colours by inserting its own [`SlimePalette`]. How many is picked in the
+//! settings and stored with each replay's
+//! [`Conditions`](crate::replay::Conditions).
+
+use crate::{Enemy, EnemyColor, EnemyPolarity};
+use bevy::color::palettes::css;
+use bevy::prelude::*;
+use rand::Rng;
+
+/// One slime colour. Positive slimes use the light variant and negative slimes
+/// the dark one.
+#[derive(Clone, Debug)]
+pub struct SlimeColor {
+	pub name: String,
+	pub base: Color,
+	pub light: Color,
+	pub dark: Color,
+}
+
+impl SlimeColor {
+	pub fn new(name: impl Into<String>, base: Color, light: Color, dark: Color) -> Self {
+		Self {
+			name: name.into(),
+			base,
+			light,
+			dark,
+		}
+	}
+}
+
+/// How many colours are in play unless the settings say otherwise.
+pub const DEFAULT_COLORS: usize = 3;
+/// The fewest colours the settings offer; one colour is no puzzle at all.
+pub const MIN_COLORS: usize = 2;
+/// How many built-in colours there are.
+pub const MAX_COLORS: usize = 5;
+
+#[derive(Resource, Clone, Debug)]
+pub struct SlimePalette {
+	colors: Vec<SlimeColor>,
+}
+
+impl Default for SlimePalette {
+	fn default() -> Self {
+		Self::with_colors(DEFAULT_COLORS)
+	}
+}
+
+impl SlimePalette {
+	pub fn new(colors: Vec<SlimeColor>) -> Self {
+		assert!(!colors.is_empty(), "a slime palette needs at least one colour");
+		Self { colors }
+	}
+
+	/// The first `count` of the built-in colours: red, green, blue, gold and
+	/// purple.
+	pub fn with_colors(count: usize) -> Self {
+		let base = Color::from(css::INDIAN_RED);
+		let red = SlimeColor::new("Red", base, base.lighter(0.03), base.darker(0.16));
+		let base = Color::from(css::FOREST_GREEN);
+		let green = SlimeColor::new(
+			"Green",
+			base,
+			base.lighter(0.05).with_saturation(1.01),
+			base.darker(0.05),
+		);
+		let base = Color::from(css::CORNFLOWER_BLUE);
+		let blue = SlimeColor::new(
+			"Blue",
+			base,
+			base.with_saturation(0.98).lighter(0.08),
+			base.darker(0.20).with_saturation(0.98),
+		);
+		let base = Color::from(css::GOLDENROD);
+		let gold = SlimeColor::new("Gold", base, base.lighter(0.08), base.darker(0.18));
+		let base = Color::from(css::MEDIUM_PURPLE);
+		let purple =
+			SlimeColor::new("Purple", base, base.lighter(0.06), base.darker(0.2));
+
+		let mut colors = vec![red, green, blue, gold, purple];
+		colors.truncate(count.max(1));
+		Self::new(colors)
+	}
+
+	pub fn len(&self) -> usize {
+		self.colors.len()
+	}
+
+	pub fn get(&self, color: EnemyColor) -> &SlimeColor {
+		&self.colors[color.0 % self.colors.len()]
+	}
+
+	/// Every colour in play, in balance order.
+	pub fn colors(&self) -> impl Iterator<Item = EnemyColor> {
+		(0..self.colors.len()).map(EnemyColor)
+	}
+
+	pub fn random_color(&self, rng: &mut impl Rng) -> EnemyColor {
+		EnemyColor(rng.gen_range(0..self.colors.len()))
+	}
+
+	/// The tint of a slime with this colour and polarity.
+	pub fn tint(&self, color: EnemyColor, polarity: EnemyPolarity) -> Color {
+		let color = self.get(color);
+		match polarity {
+			EnemyPolarity::Positive => color.light,
+			EnemyPolarity::Negative => color.dark,
+		}
+	}
+
+	pub fn color_of(&self, enemy: &Enemy) -> Color {
+		self.tint(enemy.enemy_color, enemy.enemy_polarity)
+	}
+}
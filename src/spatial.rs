@@ -0,0 +1,119 @@
+//! A uniform grid over slime positions, rebuilt every tick, so neighbour and
+//! range checks only look at nearby cells instead of every slime.
+//!
+//! This module only depends on Bevy so the benchmarks can build it on its own.
+
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Resource, Debug)]
+pub struct SpatialIndex {
+	cell_size: f32,
+	cells: HashMap<IVec2, Vec<(Entity, Vec2)>>,
+	/// Every entry in the order it was added, since the cells iterate in no
+	/// fixed order.
+	entries: Vec<(Entity, Vec2)>,
+}
+
+impl Default for SpatialIndex {
+	fn default() -> Self {
+		Self::new(64.0)
+	}
+}
+
+impl SpatialIndex {
+	pub fn new(cell_size: f32) -> Self {
+		assert!(cell_size > 0.0, "spatial index cells need a positive size");
+		Self {
+			cell_size,
+			cells: HashMap::new(),
+			entries: Vec::new(),
+		}
+	}
+
+	fn cell(&self, position: Vec2) -> IVec2 {
+		(position / self.cell_size).floor().as_ivec2()
+	}
+
+	/// Replaces the contents of the index, keeping the cell allocations around
+	/// for the next rebuild.
+	pub fn rebuild(&mut self, entries: impl IntoIterator<Item = (Entity, Vec2)>) {
+		for cell in self.cells.values_mut() {
+			cell.clear();
+		}
+		self.entries.clear();
+		for (entity, position) in entries {
+			let cell = self.cell(position);
+			self.cells.entry(cell).or_default().push((entity, position));
+			self.entries.push((entity, position));
+		}
+	}
+
+	/// Every entry, in the order it was added.
+	pub fn iter(&self) -> impl Iterator<Item = (Entity, Vec2)> + '_ {
+		self.entries.iter().copied()
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	/// Every entry within `radius` of `center`, inclusive. Entries in the same
+	/// cell come out in the order they were added.
+	pub fn within(
+		&self,
+		center: Vec2,
+		radius: f32,
+	) -> impl Iterator<Item = (Entity, Vec2)> + '_ {
+		let min = self.cell(center - Vec2::splat(radius));
+		let max = self.cell(center + Vec2::splat(radius));
+		(min.x..=max.x)
+			.flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+			.filter_map(move |cell| self.cells.get(&cell))
+			.flatten()
+			.copied()
+			.filter(move |(_, position)| position.distance(center) <= radius)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn within_matches_a_brute_force_search() {
+		let entries: Vec<(Entity, Vec2)> = (0..400)
+			.map(|i| {
+				let position = Vec2::new(
+					((i * 37) % 200) as f32 * 3.1 - 300.0,
+					((i * 91) % 170) as f32 * 2.7 - 200.0,
+				);
+				(Entity::from_raw(i), position)
+			})
+			.collect();
+		let mut index = SpatialIndex::new(25.0);
+		index.rebuild(entries.iter().copied());
+		assert_eq!(index.len(), entries.len());
+		assert!(index.iter().eq(entries.iter().copied()));
+
+		for (center, radius) in [
+			(Vec2::ZERO, 30.0),
+			(Vec2::new(-120.0, 40.0), 75.0),
+			(Vec2::new(250.0, -190.0), 5.0),
+		] {
+			let mut found: Vec<Entity> =
+				index.within(center, radius).map(|(e, _)| e).collect();
+			let mut expected: Vec<Entity> = entries
+				.iter()
+				.filter(|(_, position)| position.distance(center) <= radius)
+				.map(|(e, _)| *e)
+				.collect();
+			found.sort();
+			expected.sort();
+			assert_eq!(found, expected);
+		}
+
+		index.rebuild([(Entity::from_raw(0), Vec2::ZERO)]);
+		assert_eq!(index.len(), 1);
+	}
+}
@@ -0,0 +1,538 @@
+//! Drives a [`headless_app`] from tests with scripted key presses and clicks on
+//! specific slimes, one fixed tick per frame.
+
+use crate::archetype::Archetype;
+use crate::boss::Boss;
+use crate::chain::{ChainGraph, Chained};
+use crate::daily::{DAILY_CONDITIONS, Daily, begin_daily, end_daily};
+use crate::enemy::{SpawnView, spawn_slime};
+use crate::game_mode::{GameMode, RunClock, TIME_ATTACK_LENGTH};
+use crate::menus::GameState;
+use crate::replay::{Recording, begin_replay};
+use crate::shop::{
+	ChainRadiusLevel, PurchaseUpgrade, SlimeSlownessLevel, TotalPoints, Upgrade,
+};
+use crate::tick_input::SlimeId;
+use crate::{
+	ChainBalance, Enemy, EnemyColor, EnemyPolarity, MaxInternalVelocity, Player, Score,
+	headless_app,
+};
+use bevy::input::ButtonState;
+use bevy::input::keyboard::{Key, KeyboardInput, NativeKey};
+use bevy::picking::backend::HitData;
+use bevy::picking::events::{Pointer, Pressed};
+use bevy::picking::pointer::{Location, PointerButton, PointerId};
+use bevy::prelude::*;
+use bevy::render::camera::{ManualTextureViewHandle, NormalizedRenderTarget};
+use bevy::time::TimeUpdateStrategy;
+use chrono::NaiveDate;
+use std::time::Duration;
+
+/// Every frame advances time by exactly one simulation tick.
+const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+pub struct TestGame {
+	app: App,
+}
+
+impl TestGame {
+	/// An Endless run that has just started, with the player spawned.
+	pub fn new() -> Self {
+		Self::with_mode(GameMode::Endless)
+	}
+
+	pub fn with_mode(mode: GameMode) -> Self {
+		let mut app = headless_app();
+		app.insert_resource(TimeUpdateStrategy::ManualDuration(FRAME));
+		app.insert_resource(mode);
+		let mut game = Self { app };
+		game.step(1);
+		game
+	}
+
+	pub fn world(&self) -> &World {
+		self.app.world()
+	}
+
+	pub fn world_mut(&mut self) -> &mut World {
+		self.app.world_mut()
+	}
+
+	pub fn step(&mut self, frames: usize) {
+		for _ in 0..frames {
+			self.app.update();
+		}
+	}
+
+	/// Holds `key` down from the next frame on.
+	pub fn press(&mut self, key: KeyCode) {
+		self.send_key(key, ButtonState::Pressed);
+	}
+
+	pub fn release(&mut self, key: KeyCode) {
+		self.send_key(key, ButtonState::Released);
+	}
+
+	/// Presses and releases `key`, stepping a frame for each.
+	pub fn tap(&mut self, key: KeyCode) {
+		self.press(key);
+		self.step(1);
+		self.release(key);
+		self.step(1);
+	}
+
+	fn send_key(&mut self, key_code: KeyCode, state: ButtonState) {
+		self.world_mut().send_event(KeyboardInput {
+			key_code,
+			logical_key: Key::Unidentified(NativeKey::Unidentified),
+			state,
+			text: None,
+			repeat: false,
+			window: Entity::PLACEHOLDER,
+		});
+	}
+
+	/// Left-clicks `slime`, the way picking would report it.
+	pub fn click(&mut self, slime: Entity) {
+		self.press_pointer(slime, PointerButton::Primary);
+	}
+
+	pub fn right_click(&mut self, slime: Entity) {
+		self.press_pointer(slime, PointerButton::Secondary);
+	}
+
+	fn press_pointer(&mut self, target: Entity, button: PointerButton) {
+		let pointer = Pointer {
+			target,
+			pointer_id: PointerId::Mouse,
+			pointer_location: Location {
+				target: NormalizedRenderTarget::TextureView(ManualTextureViewHandle(0)),
+				position: Vec2::ZERO,
+			},
+			event: Pressed {
+				button,
+				hit: HitData::new(Entity::PLACEHOLDER, 0.0, None, None),
+			},
+		};
+		self.world_mut().trigger_targets(pointer, target);
+		self.world_mut().flush();
+	}
+
+	/// Spawns a slime `offset` away from the player.
+	pub fn spawn_slime(
+		&mut self,
+		offset: Vec2,
+		color: usize,
+		polarity: EnemyPolarity,
+		magnitude: u8,
+	) -> Entity {
+		let position = self.player_position() + offset;
+		let enemy = Enemy {
+			enemy_color: EnemyColor(color),
+			enemy_polarity: polarity,
+			magnitude,
+		};
+		let world = self.world_mut();
+		let speed = MaxInternalVelocity(1.0);
+		let slime = spawn_slime(&mut world.commands(), position, enemy, speed);
+		world.flush();
+		slime
+	}
+
+	pub fn player_position(&mut self) -> Vec2 {
+		let world = self.world_mut();
+		let transform = world
+			.query_filtered::<&Transform, With<Player>>()
+			.single(world)
+			.expect("the player should be spawned");
+		transform.translation.xy()
+	}
+
+	pub fn score(&self) -> u32 {
+		self.world().resource::<Score>().0
+	}
+
+	pub fn state(&self) -> GameState {
+		*self.world().resource::<State<GameState>>().get()
+	}
+
+	/// Links of the active chain, newest first.
+	pub fn chain(&self) -> Vec<Entity> {
+		self.world().resource::<ChainGraph>().chain().links().to_vec()
+	}
+
+	pub fn is_chained(&self, slime: Entity) -> bool {
+		self.world().get::<Chained>(slime).is_some()
+	}
+
+	pub fn exists(&self, entity: Entity) -> bool {
+		self.world().get_entity(entity).is_ok()
+	}
+
+	pub fn buy(&mut self, upgrade: Upgrade) {
+		self.world_mut().trigger(PurchaseUpgrade(upgrade));
+		self.world_mut().flush();
+	}
+
+	/// Every slime, oldest first.
+	pub fn slimes(&mut self) -> Vec<Entity> {
+		self.slime_positions().into_iter().map(|(slime, ..)| slime).collect()
+	}
+
+	/// Where every slime is, oldest first.
+	pub fn slime_positions(&mut self) -> Vec<(Entity, SlimeId, Vec2)> {
+		let world = self.world_mut();
+		let mut slimes: Vec<_> = world
+			.query_filtered::<(Entity, &SlimeId, &Transform), With<Enemy>>()
+			.iter(world)
+			.map(|(slime, id, transform)| (slime, *id, transform.translation.xy()))
+			.collect();
+		slimes.sort_unstable_by_key(|(_, id, _)| *id);
+		slimes
+	}
+
+	/// The run recorded so far.
+	pub fn recording(&self) -> &Recording {
+		self.world().resource::<Recording>()
+	}
+
+	/// Leaves the current run and starts watching `recording`.
+	pub fn replay(&mut self, recording: Recording) {
+		self.world_mut()
+			.resource_mut::<NextState<GameState>>()
+			.set(GameState::MainMenu);
+		self.step(1);
+		begin_replay(self.world_mut(), recording);
+		self.step(1);
+	}
+}
+
+/// Enough frames for any short chain to finish detonating.
+const DETONATION_FRAMES: usize = 150;
+
+/// Spawns slimes behind the player, lets the index pick them up and chains
+/// them in order. The player then runs away so they can't catch up.
+fn chain_slimes(
+	game: &mut TestGame,
+	slimes: &[(usize, EnemyPolarity, u8)],
+) -> Vec<Entity> {
+	let slimes: Vec<Entity> = slimes
+		.iter()
+		.enumerate()
+		.map(|(i, &(color, polarity, magnitude))| {
+			let offset = Vec2::new(-90.0, 20.0 * i as f32 - 20.0);
+			game.spawn_slime(offset, color, polarity, magnitude)
+		})
+		.collect();
+	game.step(2);
+	for &slime in &slimes {
+		game.click(slime);
+	}
+	game.press(KeyCode::KeyD);
+	game.step(1);
+	slimes
+}
+
+#[test]
+fn clicking_a_slime_in_range_chains_it() {
+	let mut game = TestGame::new();
+	let near = game.spawn_slime(Vec2::new(-90.0, 0.0), 0, EnemyPolarity::Positive, 1);
+	let far = game.spawn_slime(Vec2::new(-900.0, 0.0), 0, EnemyPolarity::Positive, 1);
+	game.step(2);
+
+	game.click(near);
+	game.click(far);
+	game.step(1);
+	assert!(game.is_chained(near));
+	assert!(!game.is_chained(far));
+	assert_eq!(game.chain(), vec![near]);
+}
+
+#[test]
+fn balanced_chain_detonates_for_points() {
+	let mut game = TestGame::new();
+	let slimes = chain_slimes(
+		&mut game,
+		&[(0, EnemyPolarity::Positive, 1), (0, EnemyPolarity::Negative, 1)],
+	);
+	assert_eq!(game.chain().len(), 2);
+
+	game.tap(KeyCode::Space);
+	game.step(DETONATION_FRAMES);
+	assert_eq!(game.state(), GameState::Game);
+	assert!(slimes.iter().all(|slime| !game.exists(*slime)));
+	assert!(game.chain().is_empty());
+	// Two pops take the combo from 1 to 5.
+	assert_eq!(game.score(), 5);
+}
+
+#[test]
+fn despawning_a_middle_link_joins_its_neighbours() {
+	let mut game = TestGame::new();
+	let slimes = chain_slimes(
+		&mut game,
+		&[
+			(0, EnemyPolarity::Positive, 1),
+			(0, EnemyPolarity::Positive, 1),
+			(0, EnemyPolarity::Negative, 2),
+		],
+	);
+	assert_eq!(game.world().resource::<ChainBalance>().0[0], 0);
+
+	game.world_mut().entity_mut(slimes[1]).despawn();
+	game.step(1);
+	assert_eq!(game.chain(), vec![slimes[2], slimes[0]]);
+	let graph = game.world().resource::<ChainGraph>();
+	assert_eq!(
+		graph.edges(0).collect::<Vec<_>>(),
+		vec![(graph.anchor(), slimes[2]), (slimes[2], slimes[0])]
+	);
+	assert_eq!(game.world().resource::<ChainBalance>().0[0], -1);
+}
+
+#[test]
+fn armour_takes_a_blast_and_splitters_split() {
+	let mut game = TestGame::new();
+	let armored =
+		game.spawn_slime(Vec2::new(-90.0, -20.0), 0, EnemyPolarity::Positive, 2);
+	let splitter =
+		game.spawn_slime(Vec2::new(-90.0, 20.0), 0, EnemyPolarity::Negative, 2);
+	game.world_mut().entity_mut(armored).insert(Archetype::Armored);
+	game.world_mut().entity_mut(splitter).insert(Archetype::Splitter);
+	game.step(2);
+	game.click(armored);
+	game.click(splitter);
+	game.press(KeyCode::KeyD);
+	game.step(1);
+
+	game.tap(KeyCode::Space);
+	game.step(DETONATION_FRAMES);
+	assert!(game.exists(armored));
+	assert!(!game.is_chained(armored));
+	assert_eq!(game.world().get::<Archetype>(armored), Some(&Archetype::Plain));
+	assert!(!game.exists(splitter));
+	// Clusters hatch well clear of other slimes, so anything close is a half.
+	let positions = game.slime_positions();
+	let near = positions.iter().find(|(slime, ..)| *slime == armored).unwrap().2;
+	let halves: Vec<Enemy> = positions
+		.iter()
+		.filter(|(slime, _, at)| *slime != armored && at.distance(near) < 250.0)
+		.map(|(slime, ..)| *game.world().get::<Enemy>(*slime).unwrap())
+		.collect();
+	assert_eq!(halves.len(), 2);
+	assert!(halves.iter().all(|half| half.magnitude == 1));
+}
+
+/// Chains a boss of magnitude 5 with slimes that balance it out and
+/// detonates them.
+fn detonate_boss(game: &mut TestGame, health: u32) -> Entity {
+	let slimes = chain_slimes(
+		game,
+		&[
+			(0, EnemyPolarity::Positive, 5),
+			(0, EnemyPolarity::Negative, 3),
+			(0, EnemyPolarity::Negative, 2),
+		],
+	);
+	let boss = slimes[0];
+	game.world_mut()
+		.entity_mut(boss)
+		.insert((Archetype::Boss, Boss::new(health)));
+	game.tap(KeyCode::Space);
+	game.step(DETONATION_FRAMES);
+	boss
+}
+
+#[test]
+fn bosses_survive_until_their_health_runs_out() {
+	let mut game = TestGame::new();
+	let boss = detonate_boss(&mut game, 2);
+	assert!(game.exists(boss));
+	assert!(!game.is_chained(boss));
+	assert_eq!(game.world().get::<Boss>(boss).unwrap().health, 1);
+
+	let mut game = TestGame::new();
+	let boss = detonate_boss(&mut game, 1);
+	assert!(!game.exists(boss));
+	// Three pops score 7, and the boss 25 more.
+	assert_eq!(game.score(), 32);
+}
+
+#[test]
+fn parked_chains_wait_while_another_detonates() {
+	let mut game = TestGame::new();
+	let first = [
+		game.spawn_slime(Vec2::new(-90.0, -20.0), 0, EnemyPolarity::Positive, 1),
+		game.spawn_slime(Vec2::new(-90.0, 0.0), 0, EnemyPolarity::Negative, 1),
+	];
+	let parked =
+		game.spawn_slime(Vec2::new(-90.0, 20.0), 1, EnemyPolarity::Positive, 1);
+	game.step(2);
+	game.click(first[0]);
+	game.click(first[1]);
+	game.step(1);
+	game.tap(KeyCode::Tab);
+	game.click(parked);
+	game.press(KeyCode::KeyD);
+	game.step(1);
+	let graph = game.world().resource::<ChainGraph>();
+	assert_eq!((graph.len(), graph.active()), (2, 1));
+	assert_eq!(game.chain(), vec![parked]);
+
+	// Back to the first chain, parking the new one, and set it off.
+	game.tap(KeyCode::Tab);
+	assert_eq!(game.chain(), vec![first[1], first[0]]);
+	game.tap(KeyCode::Space);
+	game.step(DETONATION_FRAMES);
+	assert!(first.iter().all(|slime| !game.exists(*slime)));
+	assert!(game.is_chained(parked));
+	assert_eq!(game.score(), 5);
+
+	// A parked chain that loses its last link is dropped.
+	game.world_mut().entity_mut(parked).despawn();
+	game.step(1);
+	let graph = game.world().resource::<ChainGraph>();
+	assert_eq!((graph.len(), graph.active()), (1, 0));
+	assert!(game.chain().is_empty());
+}
+
+#[test]
+fn unbalanced_chain_does_not_detonate() {
+	let mut game = TestGame::new();
+	let slimes = chain_slimes(
+		&mut game,
+		&[(0, EnemyPolarity::Positive, 2), (0, EnemyPolarity::Negative, 1)],
+	);
+
+	game.tap(KeyCode::Space);
+	game.step(DETONATION_FRAMES);
+	assert_eq!(game.state(), GameState::Game);
+	assert!(slimes.iter().all(|slime| game.is_chained(*slime)));
+	assert_eq!(game.score(), 0);
+}
+
+#[test]
+fn q_and_right_click_release_links() {
+	let mut game = TestGame::new();
+	let slimes = chain_slimes(
+		&mut game,
+		&[
+			(0, EnemyPolarity::Positive, 1),
+			(1, EnemyPolarity::Positive, 1),
+			(2, EnemyPolarity::Positive, 1),
+		],
+	);
+	assert_eq!(game.chain(), vec![slimes[2], slimes[1], slimes[0]]);
+
+	game.tap(KeyCode::KeyQ);
+	assert_eq!(game.chain(), vec![slimes[1], slimes[0]]);
+	assert!(!game.is_chained(slimes[2]));
+
+	// Cutting at a link releases it and everything chained after it.
+	game.right_click(slimes[0]);
+	game.step(1);
+	assert!(game.chain().is_empty());
+	assert!(slimes.iter().all(|slime| !game.is_chained(*slime)));
+}
+
+#[test]
+fn touching_a_slime_ends_the_run_in_the_shop() {
+	let mut game = TestGame::new();
+	game.world_mut().resource_mut::<TotalPoints>().0 = 4;
+	game.spawn_slime(Vec2::ZERO, 0, EnemyPolarity::Positive, 1);
+	game.step(3);
+	assert_eq!(game.state(), GameState::Shop);
+	assert_eq!(game.world().resource::<TotalPoints>().0, 4);
+}
+
+#[test]
+fn zen_slimes_are_harmless() {
+	let mut game = TestGame::with_mode(GameMode::Zen);
+	game.spawn_slime(Vec2::ZERO, 0, EnemyPolarity::Positive, 1);
+	game.step(3);
+	assert_eq!(game.state(), GameState::Game);
+}
+
+#[test]
+fn time_attack_ends_when_time_is_up() {
+	let mut game = TestGame::with_mode(GameMode::TimeAttack);
+	game.world_mut().resource_mut::<RunClock>().0 = TIME_ATTACK_LENGTH - FRAME * 2;
+	game.step(1);
+	assert_eq!(game.state(), GameState::Game);
+	game.step(3);
+	assert_eq!(game.state(), GameState::Shop);
+}
+
+#[test]
+fn shop_purchases_spend_total_points() {
+	let mut game = TestGame::new();
+	game.world_mut().resource_mut::<TotalPoints>().0 = 10;
+
+	game.buy(Upgrade::ChainRadius);
+	assert_eq!(game.world().resource::<ChainRadiusLevel>().0, 2);
+	assert_eq!(game.world().resource::<TotalPoints>().0, 7);
+
+	game.buy(Upgrade::ChainRadius);
+	assert_eq!(game.world().resource::<ChainRadiusLevel>().0, 3);
+	assert_eq!(game.world().resource::<TotalPoints>().0, 1);
+
+	// Not enough points left.
+	game.buy(Upgrade::SlimeSlowness);
+	assert_eq!(game.world().resource::<SlimeSlownessLevel>().0, 1);
+	assert_eq!(game.world().resource::<TotalPoints>().0, 1);
+}
+
+/// Where everything is after the `ticks`th tick of a run.
+fn snapshot(game: &mut TestGame, ticks: usize) -> (Vec2, Vec<(SlimeId, Vec2)>, u32) {
+	while game.recording().ticks.len() < ticks && game.state() == GameState::Game {
+		game.step(1);
+	}
+	assert_eq!(game.recording().ticks.len(), ticks);
+	let slimes = game
+		.slime_positions()
+		.into_iter()
+		.map(|(_, id, position)| (id, position))
+		.collect();
+	(game.player_position(), slimes, game.score())
+}
+
+#[test]
+fn a_recorded_run_replays_the_same_way() {
+	let mut game = TestGame::new();
+	game.press(KeyCode::KeyA);
+	game.step(150);
+	game.release(KeyCode::KeyA);
+	game.press(KeyCode::KeyW);
+	game.step(30);
+	// Mostly out of range, but every click is recorded all the same.
+	for slime in game.slimes() {
+		game.click(slime);
+	}
+	let oldest = game.slimes()[0];
+	game.right_click(oldest);
+	game.tap(KeyCode::Space);
+	game.tap(KeyCode::Tab);
+	game.step(100);
+	let ticks = game.recording().ticks.len();
+	let live = snapshot(&mut game, ticks);
+	assert!(!live.1.is_empty());
+	let recording = game.recording().clone();
+
+	let mut replay = TestGame::new();
+	replay.replay(recording.clone());
+	assert_eq!(snapshot(&mut replay, ticks), live);
+	assert_eq!(replay.recording(), &recording);
+}
+
+#[test]
+fn daily_runs_spawn_against_a_fixed_view() {
+	let mut game = TestGame::new();
+	let window = UVec2::new(3000, 2000);
+	game.world_mut().resource_mut::<SpawnView>().0 = window;
+	let day = Daily(NaiveDate::from_ymd_opt(2026, 10, 18).unwrap());
+
+	begin_daily(game.world_mut(), day);
+	assert_eq!(game.world().resource::<SpawnView>().0, DAILY_CONDITIONS.view);
+	end_daily(game.world_mut());
+	assert_eq!(game.world().resource::<SpawnView>().0, window);
+}
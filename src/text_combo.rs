@@ -1,10 +1,27 @@
+use crate::events::SlimePopped;
 use bevy::prelude::*;
 use bevy_defer::{AsyncAccess, AsyncCommandsExtension, AsyncWorld};
 
 pub struct TextComboPlugin;
 impl Plugin for TextComboPlugin {
 	fn build(&self, app: &mut App) {
-		app.add_systems(Update, on_text_combo_added);
+		app.add_systems(Update, (spawn_text_combo, on_text_combo_added).chain());
+	}
+}
+
+fn spawn_text_combo(mut commands: Commands, mut popped: EventReader<SlimePopped>) {
+	for SlimePopped {
+		position,
+		combo_index,
+		..
+	} in popped.read()
+	{
+		commands.spawn((
+			Transform::from_translation(*position),
+			Text2d::new(combo_index.to_string()),
+			TextLayout::new_with_justify(JustifyText::Center),
+			TextCombo,
+		));
 	}
 }
 
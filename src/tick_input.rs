@@ -0,0 +1,168 @@
+//! Player input reaches the simulation one fixed tick at a time as a
+//! [`TickInput`]. Keys and clicks are gathered into [`PendingInput`] every frame
+//! and handed to the next tick, so gameplay never reads the keyboard or mouse
+//! itself and a replay can feed the same systems.
+
+use crate::Enemy;
+use crate::menus::{GameState, PauseMenu};
+use crate::replay::Playback;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use std::mem;
+
+pub struct TickInputPlugin;
+impl Plugin for TickInputPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<SlimeIds>();
+		app.init_resource::<PendingInput>();
+		app.init_resource::<TickInput>();
+		app.add_observer(assign_slime_id);
+		app.add_systems(OnEnter(GameState::Game), reset_input);
+		app.add_systems(
+			PreUpdate,
+			gather_input
+				.after(InputSystem)
+				.run_if(in_state(GameState::Game))
+				.run_if(in_state(PauseMenu::Unpaused))
+				.run_if(in_state(Playback::Live)),
+		);
+		app.add_systems(
+			FixedPreUpdate,
+			take_tick_input
+				.run_if(in_state(GameState::Game))
+				.run_if(in_state(PauseMenu::Unpaused)),
+		);
+	}
+}
+
+/// Which slime this is within a run, counted in spawn order, so a recorded
+/// click still finds the same slime when it is replayed.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SlimeId(pub u32);
+
+/// The next [`SlimeId`] to hand out.
+#[derive(Resource, Default)]
+pub struct SlimeIds(u32);
+
+/// Movement keys held during a tick, one bit per direction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HeldKeys(pub u8);
+
+impl HeldKeys {
+	pub const UP: u8 = 1;
+	pub const DOWN: u8 = 1 << 1;
+	pub const LEFT: u8 = 1 << 2;
+	pub const RIGHT: u8 = 1 << 3;
+
+	const BINDINGS: [(KeyCode, u8); 4] = [
+		(KeyCode::KeyW, Self::UP),
+		(KeyCode::KeyS, Self::DOWN),
+		(KeyCode::KeyA, Self::LEFT),
+		(KeyCode::KeyD, Self::RIGHT),
+	];
+
+	pub fn from_keyboard(keyboard: &ButtonInput<KeyCode>) -> Self {
+		Self(
+			Self::BINDINGS
+				.iter()
+				.filter(|(key, _)| keyboard.pressed(*key))
+				.fold(0, |bits, (_, bit)| bits | bit),
+		)
+	}
+
+	pub fn contains(self, bit: u8) -> bool {
+		self.0 & bit != 0
+	}
+
+	/// Where the held keys point, not normalized.
+	pub fn direction(self) -> Vec2 {
+		let axis = |negative, positive| {
+			(self.contains(positive) as i32 - self.contains(negative) as i32) as f32
+		};
+		Vec2::new(axis(Self::LEFT, Self::RIGHT), axis(Self::DOWN, Self::UP))
+	}
+}
+
+/// A slime the player clicked, and with which button.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlimeClick {
+	pub slime: SlimeId,
+	pub button: PointerButton,
+}
+
+/// Everything the player did during one tick.
+#[derive(Resource, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TickInput {
+	pub held: HeldKeys,
+	/// Space, to detonate the active chain.
+	pub detonate: bool,
+	/// Q, to detach the newest link.
+	pub detach: bool,
+	/// Tab, to switch the active chain.
+	pub cycle_chain: bool,
+	/// Slimes clicked, in the order they were clicked.
+	pub clicks: Vec<SlimeClick>,
+}
+
+/// Input gathered since the last tick.
+#[derive(Resource, Default)]
+pub struct PendingInput(pub TickInput);
+
+fn assign_slime_id(
+	trigger: Trigger<OnAdd, Enemy>,
+	mut commands: Commands,
+	mut slime_ids: ResMut<SlimeIds>,
+) {
+	commands.entity(trigger.target()).insert(SlimeId(slime_ids.0));
+	slime_ids.0 += 1;
+}
+
+fn reset_input(
+	mut slime_ids: ResMut<SlimeIds>,
+	mut pending: ResMut<PendingInput>,
+	mut tick_input: ResMut<TickInput>,
+) {
+	*slime_ids = SlimeIds::default();
+	*pending = PendingInput::default();
+	*tick_input = TickInput::default();
+}
+
+fn gather_input(
+	keyboard: Res<ButtonInput<KeyCode>>,
+	mut pending: ResMut<PendingInput>,
+) {
+	let pending = &mut pending.0;
+	pending.held = HeldKeys::from_keyboard(&keyboard);
+	pending.detonate |= keyboard.just_pressed(KeyCode::Space);
+	pending.detach |= keyboard.just_pressed(KeyCode::KeyQ);
+	pending.cycle_chain |= keyboard.just_pressed(KeyCode::Tab);
+}
+
+/// Hands everything gathered so far to this tick. Keys stay held for any
+/// further ticks that run before the next frame gathers input again.
+pub fn take_tick_input(
+	mut pending: ResMut<PendingInput>,
+	mut tick_input: ResMut<TickInput>,
+) {
+	let held = pending.0.held;
+	*tick_input = mem::take(&mut pending.0);
+	pending.0.held = held;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn held_keys_point_the_way_they_are_pressed() {
+		let mut keyboard = ButtonInput::<KeyCode>::default();
+		keyboard.press(KeyCode::KeyW);
+		keyboard.press(KeyCode::KeyA);
+		let held = HeldKeys::from_keyboard(&keyboard);
+		assert_eq!(held, HeldKeys(HeldKeys::UP | HeldKeys::LEFT));
+		assert_eq!(held.direction(), Vec2::new(-1.0, 1.0));
+
+		keyboard.press(KeyCode::KeyD);
+		assert_eq!(HeldKeys::from_keyboard(&keyboard).direction(), Vec2::Y);
+	}
+}
@@ -57,8 +57,9 @@ fn do_tutorial(mut commands: Commands) {
 					Transform::from_translation(Vec3::new(-70.0, 0.0, 0.0))
 						.with_scale(Vec3::splat(4.0)),
 					Enemy {
-						enemy_color: EnemyColor::Red,
+						enemy_color: EnemyColor(0),
 						enemy_polarity: EnemyPolarity::Positive,
+						magnitude: 1,
 					},
 					StateScoped(GameState::Tutorial),
 				));
@@ -73,8 +74,9 @@ fn do_tutorial(mut commands: Commands) {
 					Transform::from_translation(Vec3::new(70.0, 0.0, 0.0))
 						.with_scale(Vec3::splat(4.0)),
 					Enemy {
-						enemy_color: EnemyColor::Red,
+						enemy_color: EnemyColor(0),
 						enemy_polarity: EnemyPolarity::Negative,
+						magnitude: 1,
 					},
 					StateScoped(GameState::Tutorial),
 				));
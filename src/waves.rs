@@ -0,0 +1,242 @@
+//! The wave director decides how hard the run is at any moment: how often
+//! clusters spawn, how big and how mixed they are and how fast slimes move.
+//! Each of those is a [`Ramp`] over run time or score, read from
+//! `assets/default.waves.ron`.
+//!
+//! The same file is compiled in, so the simulation never waits on the asset
+//! server. Windowed builds also load it as an asset to pick up edits while the
+//! game is running; replays of runs played with other ramps won't match.
+
+use crate::Score;
+use crate::game_mode::RunClock;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::error::Error;
+
+/// Keeps [`CurrentWave`] up to date; the enemy plugin samples it before
+/// spawning each tick.
+pub struct WaveDirectorPlugin;
+impl Plugin for WaveDirectorPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_resource::<WaveDirector>();
+		app.init_resource::<CurrentWave>();
+	}
+}
+
+/// Loads the director's ramps as an asset and swaps them in when they change.
+pub struct WaveAssetPlugin;
+impl Plugin for WaveAssetPlugin {
+	fn build(&self, app: &mut App) {
+		app.init_asset::<Waves>();
+		app.init_asset_loader::<WavesLoader>();
+		app.add_systems(Startup, load_waves);
+		app.add_systems(Update, apply_loaded_waves);
+	}
+}
+
+const DEFAULT_WAVES: &str = include_str!("../assets/default.waves.ron");
+
+/// What a [`Ramp`] is measured against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum RampInput {
+	/// Seconds since the run started.
+	Time,
+	Score,
+}
+
+/// A value that changes over the run, given as `(x, value)` points joined by
+/// straight lines. It holds its first and last values outside them.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Ramp {
+	pub over: RampInput,
+	pub points: Vec<(f32, f32)>,
+}
+
+impl Ramp {
+	pub fn sample(&self, progress: Progress) -> f32 {
+		let x = match self.over {
+			RampInput::Time => progress.seconds,
+			RampInput::Score => progress.score as f32,
+		};
+		let after = self.points.partition_point(|&(point, _)| point <= x);
+		match (after.checked_sub(1), self.points.get(after)) {
+			(None, Some(&(_, first))) => first,
+			(Some(before), None) => self.points[before].1,
+			(Some(before), Some(&(x1, y1))) => {
+				let (x0, y0) = self.points[before];
+				y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+			}
+			(None, None) => 0.0,
+		}
+	}
+}
+
+/// How far into the run we are, for sampling [`Ramp`]s.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Progress {
+	pub seconds: f32,
+	pub score: u32,
+}
+
+#[derive(Asset, TypePath, Clone, Debug, Deserialize)]
+pub struct Waves {
+	/// Seconds between clusters.
+	pub interval: Ramp,
+	/// Slimes per cluster, on average.
+	pub cluster_size: Ramp,
+	/// The chance of each further colour and charge mixed into a cluster.
+	pub color_mix: Ramp,
+	/// A multiple of the usual slime speed.
+	pub speed: Ramp,
+	/// How hard things are, from 0 to 1.
+	pub intensity: Ramp,
+}
+
+impl Waves {
+	pub fn at(&self, progress: Progress) -> WaveSettings {
+		WaveSettings {
+			interval: self.interval.sample(progress).max(0.05),
+			cluster_size: self.cluster_size.sample(progress).max(1.0),
+			color_mix: self.color_mix.sample(progress).clamp(0.0, 0.95),
+			speed: self.speed.sample(progress).max(0.0),
+			intensity: self.intensity.sample(progress).clamp(0.0, 1.0),
+		}
+	}
+}
+
+/// The ramps the simulation spawns by.
+#[derive(Resource, Clone, Debug)]
+pub struct WaveDirector(pub Waves);
+
+impl Default for WaveDirector {
+	fn default() -> Self {
+		Self(ron::from_str(DEFAULT_WAVES).expect("the built-in waves should parse"))
+	}
+}
+
+/// The director's ramps sampled at one moment of a run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WaveSettings {
+	pub interval: f32,
+	pub cluster_size: f32,
+	pub color_mix: f32,
+	pub speed: f32,
+	pub intensity: f32,
+}
+
+impl Default for WaveSettings {
+	fn default() -> Self {
+		WaveDirector::default().0.at(Progress::default())
+	}
+}
+
+/// The director's settings for this tick. `intensity` is what the HUD and
+/// music follow.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq)]
+pub struct CurrentWave(pub WaveSettings);
+
+pub fn sample_wave(
+	director: Res<WaveDirector>,
+	run_clock: Res<RunClock>,
+	score: Res<Score>,
+	mut current_wave: ResMut<CurrentWave>,
+) {
+	let settings = director.0.at(Progress {
+		seconds: run_clock.0.as_secs_f32(),
+		score: score.0,
+	});
+	current_wave.set_if_neq(CurrentWave(settings));
+}
+
+#[derive(Resource)]
+struct WavesHandle(Handle<Waves>);
+
+fn load_waves(mut commands: Commands, asset_server: Res<AssetServer>) {
+	commands.insert_resource(WavesHandle(asset_server.load("default.waves.ron")));
+}
+
+fn apply_loaded_waves(
+	mut events: EventReader<AssetEvent<Waves>>,
+	handle: Res<WavesHandle>,
+	waves: Res<Assets<Waves>>,
+	mut director: ResMut<WaveDirector>,
+) {
+	for event in events.read() {
+		if !event.is_loaded_with_dependencies(&handle.0)
+			&& !event.is_modified(&handle.0)
+		{
+			continue;
+		}
+		if let Some(waves) = waves.get(&handle.0) {
+			director.0 = waves.clone();
+		}
+	}
+}
+
+#[derive(Default, TypePath)]
+struct WavesLoader;
+
+impl AssetLoader for WavesLoader {
+	type Asset = Waves;
+	type Settings = ();
+	type Error = Box<dyn Error + Send + Sync>;
+
+	async fn load(
+		&self,
+		reader: &mut dyn Reader,
+		_settings: &(),
+		_load_context: &mut LoadContext<'_>,
+	) -> Result<Waves, Self::Error> {
+		let mut bytes = Vec::new();
+		reader.read_to_end(&mut bytes).await?;
+		Ok(ron::de::from_bytes(&bytes)?)
+	}
+
+	fn extensions(&self) -> &[&str] {
+		&["waves.ron"]
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn ramps_join_their_points_and_hold_at_the_ends() {
+		let ramp = Ramp {
+			over: RampInput::Time,
+			points: vec![(10.0, 1.0), (20.0, 3.0)],
+		};
+		let at = |seconds| ramp.sample(Progress { seconds, score: 0 });
+		assert_eq!(at(0.0), 1.0);
+		assert_eq!(at(15.0), 2.0);
+		assert_eq!(at(20.0), 3.0);
+		assert_eq!(at(100.0), 3.0);
+
+		let by_score = Ramp {
+			over: RampInput::Score,
+			..ramp
+		};
+		let progress = Progress {
+			seconds: 100.0,
+			score: 10,
+		};
+		assert_eq!(by_score.sample(progress), 1.0);
+	}
+
+	#[test]
+	fn the_built_in_waves_get_harder() {
+		let waves = WaveDirector::default().0;
+		let start = waves.at(Progress::default());
+		let later = waves.at(Progress {
+			seconds: 600.0,
+			score: 200,
+		});
+		assert!(later.interval < start.interval);
+		assert!(later.cluster_size > start.cluster_size);
+		assert!(later.speed > start.speed);
+		assert!(later.intensity > start.intensity);
+	}
+}